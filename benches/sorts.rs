@@ -1,5 +1,7 @@
+use std::convert::TryInto;
 use std::time::Instant;
 
+use algos::{Complexity, Float, GapSequence, NanPolicy, ShellSort, SortFn, Sorter};
 use criterion::BenchmarkId;
 use criterion::Criterion;
 use criterion::Throughput;
@@ -89,66 +91,473 @@ fn sorting_with(c: &mut Criterion, name: &str, mut make_items: impl FnMut(usize)
     for (size, items) in &items_set {
         let size: usize = *size;
 
-        // Use the sort from the std library as a baseline
-        // We shouldn't expect to out perform this one
-        do_sort_bench(
-            &mut group,
-            size,
-            &items,
-            "std-stable",
-            |xs: &mut [i32]| {
-                #[allow(clippy::stable_sort_primitive)]
-                xs.sort();
-            },
-        );
+        // Every registered sort, including the std library's as a baseline.
+        // We shouldn't expect to out perform those.
+        for sorter in algos::sorters::<i32>() {
+            if !sorter.requires().admits(items) {
+                continue;
+            }
+
+            do_sort_bench(
+                &mut group,
+                size,
+                items,
+                sorter.name(),
+                |xs: &mut [i32]| {
+                    sorter.sort(xs);
+                },
+            );
+        }
 
+        // Gap sequences can also be made up on the spot
+        let shell = ShellSort {
+            gaps: GapSequence::Custom(&[31, 15, 7, 3, 1]),
+        };
         do_sort_bench(
             &mut group,
             size,
-            &items,
-            "std-unstable",
+            items,
+            "shell-custom",
             |xs: &mut [i32]| {
-                xs.sort_unstable();
+                shell.sort(xs);
             },
         );
+    }
+
+    group.finish();
+}
+
+fn sorting_random_i32s(c: &mut Criterion) {
+    sorting_with(c, "n-random-items", make_random_items);
+}
+
+fn sorting_sorted_i32s(c: &mut Criterion) {
+    sorting_with(c, "n-already-sorted", make_sorted_items);
+}
+
+fn sorting_reverse_sorted_i32s(c: &mut Criterion) {
+    sorting_with(c, "n-reverse-sorted-items", make_reverse_sorted_items);
+}
+
+/// Random lowercase words, with lots of shared prefixes
+fn make_random_words(size: usize) -> Vec<String> {
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = SmallRng::seed_from_u64(u64::from_be_bytes(*b" #yolo !"));
+    (0..size)
+        .map(|_| {
+            let len = rng.gen_range(1, 12);
+            (0..len)
+                .map(|_| rng.gen_range(b'a', b'g') as char)
+                .collect()
+        })
+        .collect()
+}
+
+/// The string sorts, then every comparison sort, to compare them against
+fn string_and_comparison_sorters() -> impl Iterator<Item = SortFn<String>> {
+    algos::string_sorters()
+        .into_iter()
+        .chain(algos::comparison_sorters())
+}
+
+/// Whether `sorter` takes too long on `size` items to be worth benchmarking
+fn too_slow<T>(sorter: &dyn Sorter<T>, size: usize) -> bool {
+    sorter.worst_case() == Complexity::Quadratic && size > 1_000
+}
+
+fn sorting_random_words(c: &mut Criterion) {
+    let mut group = c.benchmark_group("n-random-words");
+
+    for size in [100, 10_000].iter().copied() {
+        let words = make_random_words(size);
+
+        group.throughput(Throughput::Elements(size as u64));
+        let mut bench = |name: &str, sort: fn(&mut [String])| {
+            group.bench_with_input(BenchmarkId::new(name, size), &words, |b, words| {
+                b.iter_batched_ref(
+                    || words.clone(),
+                    |xs| sort(xs),
+                    criterion::BatchSize::SmallInput,
+                );
+            });
+        };
+
+        for sorter in string_and_comparison_sorters() {
+            if !too_slow(&sorter, size) {
+                bench(sorter.name(), sorter.sort);
+            }
+        }
+    }
+
+    group.finish();
+}
+
+/// URLs on a few sites, which share long prefixes, like in a crawler's log
+fn make_urls(size: usize) -> Vec<String> {
+    use rand::rngs::SmallRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    let sites = [
+        "https://www.example.com",
+        "https://docs.example.com",
+        "https://en.wikipedia.org/wiki",
+        "http://blog.example.org",
+    ];
+    let sections = [
+        "articles",
+        "news/2024",
+        "news/2025",
+        "products/category",
+        "users",
+    ];
+    let slugs = [
+        "how-to-sort-strings",
+        "release-notes",
+        "overview",
+        "faq",
+        "index",
+    ];
+
+    let mut rng = SmallRng::seed_from_u64(u64::from_be_bytes(*b" #yolo !"));
+    (0..size)
+        .map(|_| {
+            format!(
+                "{}/{}/{}-{}",
+                sites.choose(&mut rng).unwrap(),
+                sections.choose(&mut rng).unwrap(),
+                slugs.choose(&mut rng).unwrap(),
+                rng.gen_range(0, 100_000),
+            )
+        })
+        .collect()
+}
+
+/// File paths in a few deep trees, like a listing of a build directory
+fn make_paths(size: usize) -> Vec<String> {
+    use rand::rngs::SmallRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    let roots = [
+        "/home/user/projects/algos/target/release/build",
+        "/home/user/projects/algos/target/debug/deps",
+        "/home/user/projects/algos/src",
+        "/usr/lib/x86_64-linux-gnu",
+    ];
+    let dirs = ["core", "alloc", "sorts", "stats", "trace", "tests"];
+    let extensions = ["rs", "rlib", "d", "o", "so"];
+
+    let mut rng = SmallRng::seed_from_u64(u64::from_be_bytes(*b" #yolo !"));
+    (0..size)
+        .map(|_| {
+            format!(
+                "{}/{}-{:016x}/{}.{}",
+                roots.choose(&mut rng).unwrap(),
+                dirs.choose(&mut rng).unwrap(),
+                rng.gen::<u64>(),
+                rng.gen_range(0, 1_000),
+                extensions.choose(&mut rng).unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// The string sorts, on strings with long shared prefixes, which comparison
+/// sorts compare over and over
+fn sorting_strings_with_prefixes(c: &mut Criterion) {
+    for (group_name, make_strings) in [
+        ("n-urls", make_urls as fn(usize) -> Vec<String>),
+        ("n-paths", make_paths),
+    ]
+    .iter()
+    .copied()
+    {
+        let mut group = c.benchmark_group(group_name);
+
+        for size in [10_000, 100_000].iter().copied() {
+            let strings = make_strings(size);
+
+            group.throughput(Throughput::Elements(size as u64));
+            let mut bench = |name: &str, sort: fn(&mut [String])| {
+                group.bench_with_input(BenchmarkId::new(name, size), &strings, |b, strings| {
+                    b.iter_batched_ref(
+                        || strings.clone(),
+                        |xs| sort(xs),
+                        criterion::BatchSize::LargeInput,
+                    );
+                });
+            };
+
+            for sorter in string_and_comparison_sorters() {
+                if !too_slow(&sorter, size) {
+                    bench(sorter.name(), sorter.sort);
+                }
+            }
+        }
+
+        group.finish();
+    }
+}
+
+/// `k` sorted streams of random items, `size` items in all
+fn make_sorted_streams(size: usize, k: usize) -> Vec<Vec<i32>> {
+    let items = make_random_items(size);
+    let mut streams: Vec<Vec<i32>> = items
+        .chunks(size.div_ceil(k))
+        .map(<[i32]>::to_vec)
+        .collect();
+    for stream in streams.iter_mut() {
+        stream.sort_unstable();
+    }
+    streams
+}
+
+/// Merges pairs of streams, then pairs of those, until there's only one left.
+fn pairwise_merge(mut streams: Vec<Vec<i32>>) -> Vec<i32> {
+    while streams.len() > 1 {
+        streams = streams
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => itertools::merge(a.iter().copied(), b.iter().copied()).collect(),
+                _ => pair[0].clone(),
+            })
+            .collect();
+    }
+    streams.pop().unwrap_or_default()
+}
 
-        do_sort_bench(&mut group, size, &items, "selection", |xs: &mut [i32]| {
-            algos::selection_sort(xs);
+fn merging_sorted_streams(c: &mut Criterion) {
+    let mut group = c.benchmark_group("k-sorted-streams");
+    let size = 100_000;
+
+    for k in [2, 16, 256].iter().copied() {
+        let streams = make_sorted_streams(size, k);
+
+        group.throughput(Throughput::Elements(size as u64));
+        let mut bench = |name: &str, merge: fn(Vec<Vec<i32>>) -> Vec<i32>| {
+            group.bench_with_input(BenchmarkId::new(name, k), &streams, |b, streams| {
+                b.iter_batched(|| streams.clone(), merge, criterion::BatchSize::LargeInput);
+            });
+        };
+
+        bench("kmerge", |streams| algos::kmerge(streams).collect());
+        bench("pairwise-merge", pairwise_merge);
+        bench("itertools-kmerge", |streams| {
+            itertools::kmerge(streams).collect()
+        });
+        bench("concat-then-sort", |streams| {
+            let mut items = streams.concat();
+            items.sort();
+            items
         });
+    }
+
+    group.finish();
+}
 
-        do_sort_bench(&mut group, size, &items, "insertion", |xs: &mut [i32]| {
-            algos::insertion_sort(xs);
+/// Sorts many small arrays of `N` random items, as in the inner loop of a bigger sort
+fn sorting_small_arrays_of<const N: usize>(
+    group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
+) {
+    let arrays: Vec<[i32; N]> = make_random_items(1_000 * N + 1)
+        .chunks_exact(N.max(1))
+        .take(1_000)
+        .map(|chunk| chunk.try_into().unwrap())
+        .collect();
+
+    group.throughput(Throughput::Elements((arrays.len() * N) as u64));
+    let mut bench = |name: &str, sort: fn(&mut [i32; N])| {
+        group.bench_with_input(BenchmarkId::new(name, N), &arrays, |b, arrays| {
+            b.iter_batched_ref(
+                || arrays.clone(),
+                |arrays| arrays.iter_mut().for_each(sort),
+                criterion::BatchSize::SmallInput,
+            );
         });
+    };
+
+    bench("network", algos::network_sort);
+    bench("insertion", |xs| algos::insertion_sort(xs));
+    bench("std-unstable", |xs| xs.sort_unstable());
+}
+
+fn sorting_small_arrays(c: &mut Criterion) {
+    let mut group = c.benchmark_group("1000-small-arrays");
+    sorting_small_arrays_of::<2>(&mut group);
+    sorting_small_arrays_of::<4>(&mut group);
+    sorting_small_arrays_of::<8>(&mut group);
+    sorting_small_arrays_of::<16>(&mut group);
+    sorting_small_arrays_of::<24>(&mut group);
+    sorting_small_arrays_of::<32>(&mut group);
+    group.finish();
+}
 
-        do_sort_bench(&mut group, size, &items, "merge", |xs: &mut [i32]| {
-            algos::merge_sort(xs);
+/// Quick sort's partition schemes, on enough random items to need lots of partitioning
+fn partitioning_random_i32s(c: &mut Criterion) {
+    use algos::{PartitionScheme, QuickSort};
+
+    let mut group = c.benchmark_group("quick-partition-random-items");
+
+    for size in [10_000, 1_000_000].iter().copied() {
+        let items = make_random_items(size);
+
+        let schemes = [
+            ("quick-lomuto", PartitionScheme::Lomuto),
+            ("quick-3way", PartitionScheme::ThreeWay),
+            ("quick-block", PartitionScheme::Block),
+        ];
+        for (name, partition) in schemes.iter().copied() {
+            let quick = QuickSort {
+                partition,
+                ..QuickSort::default()
+            };
+            do_sort_bench(&mut group, size, &items, name, |xs| quick.sort(xs));
+        }
+
+        do_sort_bench(&mut group, size, &items, "std-unstable", |xs| {
+            xs.sort_unstable()
         });
+    }
+
+    group.finish();
+}
+
+/// The parallel sorts, against the other `O(N lg N)` sorts, on enough items to be worth splitting
+fn sorting_random_i32s_in_parallel(c: &mut Criterion) {
+    use algos::{ParMergeSort, ParQuickSort};
+
+    let mut group = c.benchmark_group("n-random-items-parallel");
+    group.sample_size(10);
+
+    for size in [1_000_000, 10_000_000].iter().copied() {
+        let items = make_random_items(size);
+
+        // Every `O(N lg N)` sort, so the parallel ones can be compared with
+        // the rest, including the std library's
+        for sorter in algos::comparison_sorters::<i32>() {
+            if sorter.worst_case() == Complexity::NLogN {
+                do_sort_bench(&mut group, size, &items, sorter.name(), sorter.sort);
+            }
+        }
+
+        // The registry's parallel sorts use every CPU, but fewer can be picked
+        for threads in [2, 4].iter().copied() {
+            let merge = ParMergeSort { threads };
+            let quick = ParQuickSort {
+                threads,
+                ..ParQuickSort::default()
+            };
+            let name = |sort| format!("{}-{}", sort, threads);
+            do_sort_bench(&mut group, size, &items, &name("par-merge"), |xs| {
+                merge.sort(xs)
+            });
+            do_sort_bench(&mut group, size, &items, &name("par-quick"), |xs| {
+                quick.sort(xs)
+            });
+        }
+    }
 
-        do_sort_bench(&mut group, size, &items, "quick", |xs: &mut [i32]| {
-            algos::quick_sort(xs);
+    group.finish();
+}
+
+/// Floats of both signs, spread over many orders of magnitude
+fn make_random_floats(size: usize) -> Vec<f64> {
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = SmallRng::seed_from_u64(u64::from_be_bytes(*b" #yolo !"));
+    (0..size)
+        .map(|_| rng.gen_range(-1.0, 1.0) * 10f64.powi(rng.gen_range(-20, 20)))
+        .collect()
+}
+
+fn sorting_floats_with<F: Float + Copy>(c: &mut Criterion, name: &str, convert: fn(f64) -> F) {
+    let mut group = c.benchmark_group(name);
+
+    for size in [1_000, 100_000].iter().copied() {
+        let items: Vec<F> = make_random_floats(size).into_iter().map(convert).collect();
+
+        group.throughput(Throughput::Elements(size as u64));
+        let mut bench = |name: &str, sort: fn(&mut [F])| {
+            group.bench_with_input(BenchmarkId::new(name, size), &items, |b, items| {
+                b.iter_batched_ref(
+                    || items.clone(),
+                    |xs| sort(xs),
+                    criterion::BatchSize::SmallInput,
+                );
+            });
+        };
+
+        bench("radix", algos::radix_sort);
+        bench("sort-floats", |xs| {
+            algos::sort_floats(xs, NanPolicy::Last).unwrap()
         });
+        bench("std-unstable", |xs| xs.sort_unstable_by(F::total_cmp));
+        bench("std-stable", |xs| xs.sort_by(F::total_cmp));
     }
 
     group.finish();
 }
 
-fn sorting_random_i32s(c: &mut Criterion) {
-    sorting_with(c, "n-random-items", make_random_items);
+fn sorting_random_floats(c: &mut Criterion) {
+    sorting_floats_with(c, "n-random-f32s", |x| x as f32);
+    sorting_floats_with(c, "n-random-f64s", |x| x);
 }
 
-fn sorting_sorted_i32s(c: &mut Criterion) {
-    sorting_with(c, "n-already-sorted", make_sorted_items);
+/// A big item that's cheap to compare, but expensive to move
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct LargeItem {
+    key: i32,
+    payload: [u64; 31],
 }
 
-fn sorting_reverse_sorted_i32s(c: &mut Criterion) {
-    sorting_with(c, "n-reverse-sorted-items", make_reverse_sorted_items);
+fn sorting_large_items(c: &mut Criterion) {
+    let mut group = c.benchmark_group("n-random-large-items");
+
+    for size in [100, 1_000].iter().copied() {
+        let items: Vec<LargeItem> = make_random_items(size)
+            .into_iter()
+            .map(|key| LargeItem {
+                key,
+                payload: [key as u64; 31],
+            })
+            .collect();
+
+        group.throughput(Throughput::Elements(size as u64));
+        let mut bench = |name: &str, sort: fn(&mut [LargeItem])| {
+            group.bench_with_input(BenchmarkId::new(name, size), &items, |b, items| {
+                b.iter_batched_ref(
+                    || items.clone(),
+                    |xs| sort(xs),
+                    criterion::BatchSize::LargeInput,
+                );
+            });
+        };
+
+        for sorter in algos::comparison_sorters::<LargeItem>() {
+            bench(sorter.name(), sorter.sort);
+        }
+    }
+
+    group.finish();
 }
 
 criterion_group!(
     benches,
     sorting_random_i32s,
     sorting_sorted_i32s,
-    sorting_reverse_sorted_i32s
+    sorting_reverse_sorted_i32s,
+    sorting_random_words,
+    sorting_strings_with_prefixes,
+    sorting_random_floats,
+    sorting_large_items,
+    partitioning_random_i32s,
+    sorting_random_i32s_in_parallel,
+    merging_sorted_streams,
+    sorting_small_arrays
 );
 
 criterion_main!(benches);
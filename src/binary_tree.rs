@@ -211,14 +211,10 @@ where
                     Some(item)
                 } else {
                     // Continue searching down the left
-                    if let Some(item) = self.left.as_mut().and_then(|n| n.remove_item(item)) {
-                        // The left side found the item and removed it - continue returning it
-                        Some(item)
-                    } else {
-                        // The left side did not contain the item, therefore it isn't in our tree.
-                        // There's nothing to remove.
-                        None
-                    }
+                    // If the left side found the item and removed it, continue returning it.
+                    // If it did not contain the item, then it isn't in our tree and
+                    // there's nothing to remove.
+                    self.left.as_mut().and_then(|n| n.remove_item(item))
                 }
             }
 
@@ -236,14 +232,10 @@ where
                     Some(item)
                 } else {
                     // Continue searching down the right
-                    if let Some(item) = self.right.as_mut().and_then(|n| n.remove_item(item)) {
-                        // The right side found the item and removed it - continue returning it
-                        Some(item)
-                    } else {
-                        // The right side did not contain the item, therefore it isn't in our tree.
-                        // There's nothing to remove.
-                        None
-                    }
+                    // If the right side found the item and removed it, continue returning it.
+                    // If it did not contain the item, then it isn't in our tree and
+                    // there's nothing to remove.
+                    self.right.as_mut().and_then(|n| n.remove_item(item))
                 }
             }
         }
@@ -0,0 +1,546 @@
+//! Sorting data that's too big to fit in memory.
+//!
+//! [`ExternalSort`] is a classic external merge sort, in two phases:
+//!
+//! 1. Read as many records as fit in the memory budget, sort them with
+//!    `tim_sort`, and write them out to a temporary file as a sorted "run".
+//!    Repeat until the input is used up.
+//! 2. Merge the runs, `fan_in` at a time, until there are few enough to merge
+//!    straight into the output.
+//!
+//! Each merge pass reads and writes every record once, so a bigger fan-in
+//! means fewer passes, but a smaller read buffer for each run.
+//!
+//! ```
+//! use algos::external::{ExternalSort, RecordFormat};
+//!
+//! let input = "pear\napple\nfig\nbanana\n";
+//! let mut output = vec![];
+//!
+//! // A memory budget this small spills every couple of records to disk.
+//! let sort = ExternalSort {
+//!     memory_budget: 64,
+//!     fan_in: 2,
+//!     ..ExternalSort::default()
+//! };
+//! sort.sort(input.as_bytes(), &mut output).unwrap();
+//! assert_eq!(output, b"apple\nbanana\nfig\npear\n");
+//! ```
+use core::cmp::Ordering;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+use crate::heap::BinaryHeap;
+use crate::tim_sort_by;
+
+/// How records are laid out in the input, and in the output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Each record is a line, ending with `\n`.
+    ///
+    /// The `\n` isn't part of the record when comparing. Every line in the
+    /// output ends with `\n`, even if the last line of the input didn't.
+    #[default]
+    Lines,
+
+    /// Each record is exactly this many bytes, with nothing between them.
+    FixedWidth(usize),
+}
+
+impl RecordFormat {
+    /// Reads the next record into `record`, replacing what was there.
+    ///
+    /// Returns `false` at the end of the input.
+    fn read(self, input: &mut impl BufRead, record: &mut Vec<u8>) -> io::Result<bool> {
+        record.clear();
+        match self {
+            RecordFormat::Lines => {
+                if input.read_until(b'\n', record)? == 0 {
+                    return Ok(false);
+                }
+                if record.last() == Some(&b'\n') {
+                    record.pop();
+                }
+                Ok(true)
+            }
+            RecordFormat::FixedWidth(width) => {
+                let read = input.take(width as u64).read_to_end(record)?;
+                match read {
+                    0 => Ok(false),
+                    read if read == width => Ok(true),
+                    read => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "the input ended with a partial record of {} bytes, out of {}",
+                            read, width
+                        ),
+                    )),
+                }
+            }
+        }
+    }
+
+    fn write(self, output: &mut impl Write, record: &[u8]) -> io::Result<()> {
+        output.write_all(record)?;
+        match self {
+            RecordFormat::Lines => output.write_all(b"\n"),
+            RecordFormat::FixedWidth(_) => Ok(()),
+        }
+    }
+}
+
+/// Options for sorting more data than fits in memory.
+///
+/// See the [module docs](self) for how it works.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExternalSort {
+    pub format: RecordFormat,
+
+    /// Roughly how many bytes of records to hold in memory at once.
+    ///
+    /// Each record costs its length, plus a few words of bookkeeping. A single
+    /// record bigger than this is still sorted, in a run of its own.
+    pub memory_budget: usize,
+
+    /// How many runs to merge at once. Must be at least 2.
+    pub fan_in: usize,
+
+    /// Where to put the runs. Defaults to `std::env::temp_dir()`.
+    ///
+    /// Runs are deleted as soon as they've been merged, or if sorting fails.
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl Default for ExternalSort {
+    fn default() -> Self {
+        ExternalSort {
+            format: RecordFormat::default(),
+            memory_budget: 64 * 1024 * 1024,
+            fan_in: 16,
+            temp_dir: None,
+        }
+    }
+}
+
+/// What an [`ExternalSort`] did.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExternalSortStats {
+    /// How many records were sorted.
+    pub records: usize,
+
+    /// How many sorted runs the records were split into.
+    pub runs: usize,
+
+    /// How many times every record was read from a run and written out again.
+    /// The last merge, into the output, counts as a pass.
+    pub merge_passes: usize,
+}
+
+/// Bookkeeping for each record that we hold in memory: where it is in the
+/// buffer, and room for `tim_sort` to move that around.
+const RECORD_OVERHEAD: usize = 3 * core::mem::size_of::<usize>();
+
+impl ExternalSort {
+    /// Sorts the records from `input` by their bytes, and writes them to `output`.
+    pub fn sort<R, W>(&self, input: R, output: W) -> io::Result<ExternalSortStats>
+    where
+        R: Read,
+        W: Write,
+    {
+        self.sort_by(input, output, |a, b| a.cmp(b))
+    }
+
+    /// Sorts the records from `input` according to `compare`, and writes them to
+    /// `output`.
+    ///
+    /// This is stable: records that compare equal are written in the same
+    /// order that they were read.
+    pub fn sort_by<R, W, F>(
+        &self,
+        input: R,
+        output: W,
+        mut compare: F,
+    ) -> io::Result<ExternalSortStats>
+    where
+        R: Read,
+        W: Write,
+        F: FnMut(&[u8], &[u8]) -> Ordering,
+    {
+        assert!(
+            self.fan_in >= 2,
+            "ExternalSort needs a fan_in of at least 2"
+        );
+
+        let mut input = BufReader::new(input);
+        let mut output = BufWriter::new(output);
+        let mut stats = ExternalSortStats::default();
+
+        // 1. Split the input into sorted runs
+        let mut runs = vec![];
+        let mut chunk = Chunk::default();
+        loop {
+            let done = !chunk.fill(&mut input, self.format, self.memory_budget)?;
+            stats.records += chunk.records.len();
+
+            // Everything fit in memory, so we don't need any runs at all.
+            if done && runs.is_empty() {
+                stats.runs = 1;
+                chunk.write_sorted(&mut output, self.format, &mut compare)?;
+                output.flush()?;
+                return Ok(stats);
+            }
+
+            if !chunk.records.is_empty() {
+                let mut run = self.temp_file()?;
+                chunk.write_sorted(
+                    &mut BufWriter::new(&mut run.file),
+                    self.format,
+                    &mut compare,
+                )?;
+                runs.push(run);
+            }
+
+            if done {
+                break;
+            }
+        }
+        stats.runs = runs.len();
+
+        // 2. Merge runs until there are few enough to merge into the output.
+        // Runs stay in the same order as the input, so that ties stay stable.
+        while runs.len() > self.fan_in {
+            let mut merged = Vec::with_capacity(runs.len() / self.fan_in + 1);
+            let mut runs_left = runs.into_iter();
+            loop {
+                let group: Vec<TempFile> = runs_left.by_ref().take(self.fan_in).collect();
+                if group.is_empty() {
+                    break;
+                }
+
+                let mut run = self.temp_file()?;
+                self.merge(group, &mut BufWriter::new(&mut run.file), &mut compare)?;
+                merged.push(run);
+            }
+
+            runs = merged;
+            stats.merge_passes += 1;
+        }
+
+        self.merge(runs, &mut output, &mut compare)?;
+        output.flush()?;
+        stats.merge_passes += 1;
+
+        Ok(stats)
+    }
+
+    /// Merges the sorted `runs` into `output`, and deletes them.
+    fn merge<W, F>(&self, runs: Vec<TempFile>, output: &mut W, compare: &mut F) -> io::Result<()>
+    where
+        W: Write,
+        F: FnMut(&[u8], &[u8]) -> Ordering,
+    {
+        // Split the memory budget between a read buffer for each run
+        let buffer_size = (self.memory_budget / runs.len()).max(1);
+
+        let mut readers = vec![];
+        for mut run in runs {
+            run.file.seek(SeekFrom::Start(0))?;
+            let file = run.file.try_clone()?;
+            readers.push((BufReader::with_capacity(buffer_size, file), run));
+        }
+
+        // A min heap of the next record from each run, tagged with its run.
+        // Ties go to the earlier run, to keep the merge stable.
+        let mut heap = BinaryHeap::with_compare(|a: &(Vec<u8>, usize), b: &(Vec<u8>, usize)| {
+            compare(&b.0, &a.0).then(b.1.cmp(&a.1))
+        });
+        for (run, (reader, _)) in readers.iter_mut().enumerate() {
+            let mut record = vec![];
+            if self.format.read(reader, &mut record)? {
+                heap.push((record, run));
+            }
+        }
+
+        while let Some((mut record, run)) = heap.pop() {
+            self.format.write(output, &record)?;
+
+            // Replace it with the next record from the same run, re-using the buffer.
+            if self.format.read(&mut readers[run].0, &mut record)? {
+                heap.push((record, run));
+            }
+        }
+
+        output.flush()
+    }
+
+    /// Creates a new, empty temporary file, which is deleted when dropped.
+    fn temp_file(&self) -> io::Result<TempFile> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = self.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let id = NEXT_ID.fetch_add(1, AtomicOrdering::Relaxed);
+        let path = dir.join(format!(
+            "algos-external-sort-{}-{}.run",
+            std::process::id(),
+            id
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(TempFile { path, file })
+    }
+}
+
+/// Records that have been read into memory, to be sorted into one run.
+#[derive(Default)]
+struct Chunk {
+    /// Every record, back to back
+    bytes: Vec<u8>,
+
+    /// Where each record is in `bytes`
+    records: Vec<(usize, usize)>,
+}
+
+impl Chunk {
+    /// Replaces the records with as many from `input` as fit in `budget` bytes.
+    ///
+    /// Returns `false` if the input has run out.
+    fn fill(
+        &mut self,
+        input: &mut impl BufRead,
+        format: RecordFormat,
+        budget: usize,
+    ) -> io::Result<bool> {
+        self.bytes.clear();
+        self.records.clear();
+
+        let mut record = vec![];
+        loop {
+            if !format.read(input, &mut record)? {
+                return Ok(false);
+            }
+
+            let start = self.bytes.len();
+            self.bytes.extend_from_slice(&record);
+            self.records.push((start, self.bytes.len()));
+
+            let used = self.bytes.len() + self.records.len() * RECORD_OVERHEAD;
+            if used >= budget {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn write_sorted<W, F>(
+        &mut self,
+        output: &mut W,
+        format: RecordFormat,
+        compare: &mut F,
+    ) -> io::Result<()>
+    where
+        W: Write,
+        F: FnMut(&[u8], &[u8]) -> Ordering,
+    {
+        let bytes = &self.bytes;
+        tim_sort_by(&mut self.records, |a, b| {
+            compare(&bytes[a.0..a.1], &bytes[b.0..b.1])
+        });
+
+        for (start, end) in self.records.iter() {
+            format.write(output, &bytes[*start..*end])?;
+        }
+
+        output.flush()
+    }
+}
+
+/// A file that's deleted when dropped.
+struct TempFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // There's nothing useful to do if this fails, and it's only a temp file.
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    /// A fresh, empty directory for temp files, so tests can check it's cleaned up.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "algos-external-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn is_empty(dir: &PathBuf) -> bool {
+        fs::read_dir(dir).unwrap().next().is_none()
+    }
+
+    fn random_words(count: usize) -> Vec<String> {
+        let mut rng = SmallRng::seed_from_u64(0x5EED);
+        (0..count)
+            .map(|_| {
+                let len = rng.gen_range(0, 8);
+                (0..len)
+                    .map(|_| rng.gen_range(b'a', b'f') as char)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_sort_lines() {
+        let dir = temp_dir("lines");
+        let words = random_words(1_000);
+        let input: String = words.iter().map(|w| format!("{}\n", w)).collect();
+
+        let mut sorted = words;
+        sorted.sort();
+        let expected: String = sorted.iter().map(|w| format!("{}\n", w)).collect();
+
+        for fan_in in [2, 3, 16].iter().copied() {
+            let sort = ExternalSort {
+                memory_budget: 256,
+                fan_in,
+                temp_dir: Some(dir.clone()),
+                ..ExternalSort::default()
+            };
+
+            let mut output = vec![];
+            let stats = sort.sort(input.as_bytes(), &mut output).unwrap();
+
+            assert_eq!(String::from_utf8(output).unwrap(), expected);
+            assert_eq!(stats.records, 1_000);
+            assert!(stats.runs > fan_in, "{:?}", stats);
+            assert!(stats.merge_passes > 1, "{:?}", stats);
+            assert!(is_empty(&dir), "left runs behind in {:?}", dir);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_sort_fixed_width() {
+        let dir = temp_dir("fixed-width");
+        let mut rng = SmallRng::seed_from_u64(0x5EED);
+        let numbers: Vec<u64> = (0..2_000).map(|_| rng.gen()).collect();
+        let input: Vec<u8> = numbers.iter().flat_map(|n| n.to_be_bytes()).collect();
+
+        // Sort in descending order
+        let sort = ExternalSort {
+            format: RecordFormat::FixedWidth(8),
+            memory_budget: 1_000,
+            fan_in: 4,
+            temp_dir: Some(dir.clone()),
+        };
+        let mut output = vec![];
+        let stats = sort
+            .sort_by(&input[..], &mut output, |a, b| b.cmp(a))
+            .unwrap();
+
+        let mut expected = numbers;
+        expected.sort_unstable_by(|a, b| b.cmp(a));
+        let expected: Vec<u8> = expected.iter().flat_map(|n| n.to_be_bytes()).collect();
+        assert_eq!(output, expected);
+        assert_eq!(stats.records, 2_000);
+        assert!(is_empty(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_sort_is_stable() {
+        let dir = temp_dir("stable");
+
+        // Sort by the first letter only, so the numbers must stay in order.
+        let lines: Vec<String> = (0..500)
+            .map(|i| format!("{}{}", ['b', 'a', 'c'][i % 3], i))
+            .collect();
+        let input = lines.join("\n");
+
+        let sort = ExternalSort {
+            memory_budget: 100,
+            fan_in: 2,
+            temp_dir: Some(dir.clone()),
+            ..ExternalSort::default()
+        };
+        let mut output = vec![];
+        sort.sort_by(input.as_bytes(), &mut output, |a, b| a[0].cmp(&b[0]))
+            .unwrap();
+
+        let mut expected = lines;
+        expected.sort_by_key(|line| line.as_bytes()[0]);
+        let expected: String = expected.iter().map(|w| format!("{}\n", w)).collect();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_fits_in_memory() {
+        let dir = temp_dir("in-memory");
+        let sort = ExternalSort {
+            temp_dir: Some(dir.clone()),
+            ..ExternalSort::default()
+        };
+
+        let mut output = vec![];
+        let stats = sort.sort(&b"c\nb\n\na"[..], &mut output).unwrap();
+        assert_eq!(output, b"\na\nb\nc\n");
+        assert_eq!(
+            stats,
+            ExternalSortStats {
+                records: 4,
+                runs: 1,
+                merge_passes: 0,
+            }
+        );
+
+        let mut output = vec![];
+        let stats = sort.sort(&b""[..], &mut output).unwrap();
+        assert_eq!(output, b"");
+        assert_eq!(stats.records, 0);
+        assert!(is_empty(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_partial_record_is_an_error() {
+        let dir = temp_dir("partial");
+        let sort = ExternalSort {
+            format: RecordFormat::FixedWidth(4),
+            memory_budget: 16,
+            temp_dir: Some(dir.clone()),
+            ..ExternalSort::default()
+        };
+
+        let input = b"abcdefghijklmnopqrstuvwxyz";
+        let error = sort.sort(&input[..], vec![]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Runs that were already written are cleaned up
+        assert!(is_empty(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
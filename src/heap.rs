@@ -0,0 +1,344 @@
+use core::cmp::Ordering;
+use core::fmt::{self, Debug};
+
+use crate::stats;
+
+/// Moves the item at `node` down the heap until both of its children are
+/// no greater than it.
+///
+/// `heap` is an implicit binary tree: the children of `node` live at
+/// `2 * node + 1` and `2 * node + 2`. Everything below `node` must already be
+/// a valid heap.
+pub fn sift_down<T, F>(heap: &mut [T], mut node: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        let left = 2 * node + 1;
+        let right = left + 1;
+
+        // Pick the larger child
+        let mut child = left;
+        if right < heap.len() && compare(&heap[left], &heap[right]) == Ordering::Less {
+            child = right;
+        }
+
+        // Stop if we're a leaf, or if we're already in order.
+        if child >= heap.len() || compare(&heap[node], &heap[child]) != Ordering::Less {
+            return;
+        }
+
+        stats::swap(heap, node, child);
+        node = child;
+    }
+}
+
+/// Moves the item at `node` up the heap until its parent is no less than it.
+///
+/// Everything except `node` must already be a valid heap.
+pub fn sift_up<T, F>(heap: &mut [T], mut node: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    while node > 0 {
+        let parent = (node - 1) / 2;
+
+        if compare(&heap[parent], &heap[node]) != Ordering::Less {
+            return;
+        }
+
+        stats::swap(heap, parent, node);
+        node = parent;
+    }
+}
+
+/// Rearranges `items` into a max heap, according to `compare`.
+///
+/// This builds the heap from the bottom up, which is `O(N)`. Most nodes are
+/// near the bottom of the tree, and those don't have far to sift.
+pub fn heapify<T, F>(items: &mut [T], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Leaves are trivially heaps, so we can start with the last node that has children.
+    for node in (0..items.len() / 2).rev() {
+        sift_down(items, node, compare);
+    }
+}
+
+/// Whether `items` is a valid max heap, according to `compare`.
+pub fn is_heap<T, F>(items: &[T], mut compare: F) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    (1..items.len()).all(|node| compare(&items[(node - 1) / 2], &items[node]) != Ordering::Less)
+}
+
+/// A priority queue backed by an array.
+///
+/// The greatest item, according to `compare`, is always at the top. By default
+/// that's a max heap using `Ord`, but `BinaryHeap::new_min()` or a custom
+/// comparator can change that.
+///
+/// ```
+/// use algos::heap::BinaryHeap;
+///
+/// let mut heap: BinaryHeap<i32> = vec![3, 1, 4, 1, 5].into();
+/// assert_eq!(heap.pop(), Some(5));
+/// assert_eq!(heap.peek(), Some(&4));
+///
+/// let mut heap = BinaryHeap::new_min();
+/// heap.push(3);
+/// heap.push(1);
+/// assert_eq!(heap.pop(), Some(1));
+/// ```
+#[derive(Clone)]
+pub struct BinaryHeap<T, F = fn(&T, &T) -> Ordering> {
+    items: Vec<T>,
+    compare: F,
+}
+
+impl<T> BinaryHeap<T>
+where
+    T: Ord,
+{
+    /// Create an empty max heap
+    pub fn new() -> Self {
+        BinaryHeap::with_compare(T::cmp)
+    }
+
+    /// Create an empty min heap
+    pub fn new_min() -> Self {
+        BinaryHeap::with_compare(|a: &T, b: &T| b.cmp(a))
+    }
+}
+
+impl<T> Default for BinaryHeap<T>
+where
+    T: Ord,
+{
+    fn default() -> Self {
+        BinaryHeap::new()
+    }
+}
+
+impl<T, F> BinaryHeap<T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    /// Create an empty heap, with the greatest item according to `compare` on top.
+    pub fn with_compare(compare: F) -> Self {
+        BinaryHeap {
+            items: Vec::new(),
+            compare,
+        }
+    }
+
+    /// Create a heap out of `items` in `O(N)`.
+    pub fn from_vec_by(mut items: Vec<T>, mut compare: F) -> Self {
+        heapify(&mut items, &mut compare);
+
+        BinaryHeap { items, compare }
+    }
+
+    /// Number of items stored in this heap.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether there are any items in this heap.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the greatest item, or `None` if the heap is empty.
+    pub fn peek(&self) -> Option<&T> {
+        self.items.first()
+    }
+
+    /// Adds an item to the heap in `O(lg N)`.
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+
+        let last = self.items.len() - 1;
+        sift_up(&mut self.items, last, &mut self.compare);
+    }
+
+    /// Removes the greatest item and returns it, or `None` if the heap is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        // Move the top item to the end where we can cheaply remove it, and
+        // then fix up whatever got moved to the top in its place.
+        let last = self.items.len() - 1;
+        stats::swap(&mut self.items, 0, last);
+        let top = self.items.pop();
+        sift_down(&mut self.items, 0, &mut self.compare);
+
+        top
+    }
+
+    /// Replaces the greatest item with `item`, and returns the old greatest item.
+    ///
+    /// This is cheaper than a `pop()` followed by a `push()`, since it only sifts once.
+    pub fn push_pop(&mut self, item: T) -> T {
+        match self.items.first_mut() {
+            Some(top) if (self.compare)(top, &item) == Ordering::Greater => {
+                let top = core::mem::replace(top, item);
+                sift_down(&mut self.items, 0, &mut self.compare);
+                top
+            }
+            // Our new item would immediately be popped off again
+            _ => item,
+        }
+    }
+
+    /// Consumes the heap and returns its items in ascending order, according to `compare`.
+    ///
+    /// The item that would have been popped first is last.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        // Pop the top item off of the heap and place it after the heap.
+        for end in (1..self.items.len()).rev() {
+            stats::swap(&mut self.items, 0, end);
+            sift_down(&mut self.items[..end], 0, &mut self.compare);
+        }
+
+        self.items
+    }
+
+    /// Consumes the heap and returns its items in heap order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+}
+
+impl<T> From<Vec<T>> for BinaryHeap<T>
+where
+    T: Ord,
+{
+    fn from(items: Vec<T>) -> Self {
+        BinaryHeap::from_vec_by(items, T::cmp)
+    }
+}
+
+impl<T> std::iter::FromIterator<T> for BinaryHeap<T>
+where
+    T: Ord,
+{
+    fn from_iter<I: std::iter::IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().collect::<Vec<T>>().into()
+    }
+}
+
+impl<T, F> Debug for BinaryHeap<T, F>
+where
+    T: Debug,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BinaryHeap")
+            .field("items", &self.items)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    #[test]
+    fn check_push_pop_max() {
+        let mut heap = BinaryHeap::new();
+        assert!(heap.is_empty());
+        assert_eq!(heap.peek(), None);
+
+        for item in [3, 1, 4, 1, 5, 9, 2, 6].iter().copied() {
+            heap.push(item);
+            assert!(is_heap(&heap.items, i32::cmp));
+        }
+        assert_eq!(heap.len(), 8);
+        assert_eq!(heap.peek(), Some(&9));
+
+        let mut popped = vec![];
+        while let Some(item) = heap.pop() {
+            assert!(is_heap(&heap.items, i32::cmp));
+            popped.push(item);
+        }
+        assert_eq!(popped, [9, 6, 5, 4, 3, 2, 1, 1]);
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn check_push_pop_min() {
+        let mut heap = BinaryHeap::new_min();
+        for item in [3, 1, 4, 1, 5, 9, 2, 6].iter().copied() {
+            heap.push(item);
+        }
+
+        let mut popped = vec![];
+        while let Some(item) = heap.pop() {
+            popped.push(item);
+        }
+        assert_eq!(popped, [1, 1, 2, 3, 4, 5, 6, 9]);
+    }
+
+    #[test]
+    fn check_push_pop_by_key() {
+        let mut heap = BinaryHeap::with_compare(|a: &(&str, u32), b: &(&str, u32)| a.1.cmp(&b.1));
+        heap.push(("low", 1));
+        heap.push(("high", 10));
+        heap.push(("mid", 5));
+
+        assert_eq!(heap.pop(), Some(("high", 10)));
+        assert_eq!(heap.pop(), Some(("mid", 5)));
+        assert_eq!(heap.pop(), Some(("low", 1)));
+    }
+
+    #[test]
+    fn check_push_pop_replaces_top() {
+        let mut heap: BinaryHeap<i32> = vec![5, 3, 8].into();
+
+        // Smaller items replace the top
+        assert_eq!(heap.push_pop(1), 8);
+        assert_eq!(heap.peek(), Some(&5));
+
+        // Larger items come right back out
+        assert_eq!(heap.push_pop(10), 10);
+        assert_eq!(heap.into_sorted_vec(), [1, 3, 5]);
+    }
+
+    #[test]
+    fn check_heapify_is_linear() {
+        let mut items: Vec<i32> = (0..10_000).collect();
+        items.shuffle(&mut thread_rng());
+
+        let mut compares = 0;
+        heapify(&mut items, &mut |a: &i32, b: &i32| {
+            compares += 1;
+            a.cmp(b)
+        });
+
+        assert!(is_heap(&items, i32::cmp));
+        // Each sift down does 2 compares per level, and the levels sum to < N.
+        assert!(compares < 2 * items.len(), "{} compares", compares);
+    }
+
+    #[test]
+    fn check_into_sorted_vec() {
+        let mut items: Vec<i32> = (0..100).map(|i| i / 2).collect();
+        let sorted = items.clone();
+        items.shuffle(&mut thread_rng());
+
+        let heap: BinaryHeap<i32> = items.iter().copied().collect();
+        assert_eq!(heap.len(), items.len());
+        assert_eq!(heap.into_sorted_vec(), sorted);
+
+        let heap: BinaryHeap<i32> = BinaryHeap::new();
+        assert_eq!(heap.into_sorted_vec(), []);
+    }
+}
@@ -4,6 +4,11 @@ mod sorts;
 pub use sorts::*;
 
 pub mod binary_tree;
+pub mod external;
+pub mod heap;
+pub mod presortedness;
+pub mod stats;
+pub mod trace;
 
 #[cfg(feature = "substr")]
 pub mod substr;
@@ -0,0 +1,321 @@
+//! Measures how far from sorted some items are.
+//!
+//! Adaptive sorts like `tim_sort` do less work on items that are nearly sorted,
+//! but "nearly" can mean several things. Each measure here counts a different
+//! kind of disorder, and is zero (or one, for [`runs`]) when the items are
+//! already sorted:
+//!
+//! | Measure                | Counts                                               | Time          |
+//! |------------------------|------------------------------------------------------|---------------|
+//! | [`count_inversions`]   | pairs of items that are out of order                 | `O(N lg N)`   |
+//! | [`runs`]               | ascending runs                                       | `O(N)`        |
+//! | [`rem`]                | items to remove to leave the rest sorted             | `O(N lg N)`   |
+//! | [`max_displacement`]   | how far the furthest item is from where it belongs   | `O(N lg N)`   |
+//!
+//! ```
+//! use algos::presortedness;
+//!
+//! // One item, far from home
+//! let items = [2, 3, 4, 5, 6, 1];
+//! assert_eq!(presortedness::count_inversions(&items), 5);
+//! assert_eq!(presortedness::runs(&items), 2);
+//! assert_eq!(presortedness::rem(&items), 1);
+//! assert_eq!(presortedness::max_displacement(&items), 5);
+//! ```
+//!
+//! [`kendall_tau_distance`] compares two orderings of the same items instead.
+use core::cmp::Ordering;
+
+use crate::merge_sort_by;
+use crate::sorts::merge_helper;
+
+pub fn count_inversions<T: Ord>(items: &[T]) -> u64 {
+    count_inversions_by(items, T::cmp)
+}
+
+/// Counts the pairs of items that are out of order.
+///
+/// Sorted items have none, and reversed ones have the most, `N(N-1)/2`. Equal
+/// items are never out of order. This is also how many swaps of neighbouring
+/// items it takes to sort them, which is what `insertion_sort` does.
+///
+/// This runs `merge_sort` on references to the items, which counts the
+/// inversions between the two runs of each merge as it goes. That takes
+/// `O(N lg N)` time, and `N/2` references of scratch memory. The items are
+/// left as they were.
+pub fn count_inversions_by<T, F>(items: &[T], mut compare: F) -> u64
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut refs: Vec<&T> = items.iter().collect();
+    let mut scratch: Vec<&T> = Vec::with_capacity(refs.len() / 2);
+    let mut is_less = |a: &&T, b: &&T| compare(a, b) == Ordering::Less;
+
+    let mut inversions: u64 = 0;
+    merge_helper(
+        scratch.spare_capacity_mut(),
+        &mut refs,
+        &mut is_less,
+        &mut inversions,
+    );
+    inversions
+}
+
+pub fn runs<T: Ord>(items: &[T]) -> usize {
+    runs_by(items, T::cmp)
+}
+
+/// Counts the ascending runs in `items`, which is one more than the number of
+/// times an item is less than the one before it.
+///
+/// Sorted items are one run, and reversed ones with no ties are `N` runs. This
+/// is how many runs a natural merge sort would start with.
+pub fn runs_by<T, F>(items: &[T], mut compare: F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    if items.is_empty() {
+        return 0;
+    }
+
+    let descents = items
+        .windows(2)
+        .filter(|w| compare(&w[1], &w[0]) == Ordering::Less)
+        .count();
+    descents + 1
+}
+
+pub fn rem<T: Ord>(items: &[T]) -> usize {
+    rem_by(items, T::cmp)
+}
+
+/// Counts the fewest items that could be removed to leave the rest sorted.
+///
+/// That's `N` minus the length of the longest ascending subsequence, which is
+/// found by patience sorting: each item goes on the leftmost pile whose top is
+/// greater than it, or a new pile if there isn't one. There are as many piles
+/// as the longest subsequence is long. Only the top of each pile is kept, and
+/// they're always in order, so each item binary searches for its pile.
+///
+/// This takes `O(N lg N)` time, and `O(N)` scratch memory.
+pub fn rem_by<T, F>(items: &[T], mut compare: F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut tops: Vec<&T> = vec![];
+    for item in items {
+        // Ties can go in the same subsequence, so they start a new pile
+        let pile = tops.partition_point(|top| compare(item, top) != Ordering::Less);
+        if pile == tops.len() {
+            tops.push(item);
+        } else {
+            tops[pile] = item;
+        }
+    }
+
+    items.len() - tops.len()
+}
+
+pub fn max_displacement<T: Ord>(items: &[T]) -> usize {
+    max_displacement_by(items, T::cmp)
+}
+
+/// Finds how far the furthest item is from where it is once sorted.
+///
+/// Equal items keep their order, as a stable sort would leave them, which puts
+/// each as close to where it started as it can be. So sorted items have no
+/// displacement, even if they have ties.
+///
+/// This runs `merge_sort` on the indexes of the items, which takes
+/// `O(N lg N)` time, and `O(N)` scratch memory.
+pub fn max_displacement_by<T, F>(items: &[T], mut compare: F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    sorted_indexes_by(items, &mut compare)
+        .into_iter()
+        .enumerate()
+        .map(|(to, from)| to.abs_diff(from))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Counts the pairs of items that `a` and `b` put in a different order.
+///
+/// `a` and `b` must hold the same items, in any order. Where some are equal,
+/// the first one in `a` is matched with the first one in `b`, and so on, which
+/// gives the smallest distance. Orderings that match have no distance, and
+/// ones that are the reverse of each other have the most, `N(N-1)/2`.
+///
+/// ```
+/// use algos::presortedness::kendall_tau_distance;
+///
+/// let judge_1 = ["apple", "banana", "cherry", "date"];
+/// let judge_2 = ["banana", "apple", "cherry", "date"];
+/// assert_eq!(kendall_tau_distance(&judge_1, &judge_2), 1);
+/// ```
+///
+/// This counts the inversions in where `b`'s items are in `a`, so it takes
+/// `O(N lg N)` time, and `O(N)` scratch memory.
+///
+/// # Panics
+///
+/// Panics if `a` and `b` don't hold the same items.
+pub fn kendall_tau_distance<T: Ord>(a: &[T], b: &[T]) -> u64 {
+    assert_eq!(a.len(), b.len(), "orderings have different lengths");
+
+    // The `i`th smallest item in `b` is the `i`th smallest in `a`
+    let a_sorted = sorted_indexes_by(a, &mut T::cmp);
+    let b_sorted = sorted_indexes_by(b, &mut T::cmp);
+    let mut in_a = vec![0; b.len()];
+    for (&i, &j) in a_sorted.iter().zip(&b_sorted) {
+        assert!(a[i] == b[j], "orderings have different items");
+        in_a[j] = i;
+    }
+
+    count_inversions(&in_a)
+}
+
+/// The indexes of `items`, in stably sorted order.
+fn sorted_indexes_by<T, F>(items: &[T], compare: &mut F) -> Vec<usize>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut indexes: Vec<usize> = (0..items.len()).collect();
+    merge_sort_by(&mut indexes, |&i, &j| compare(&items[i], &items[j]));
+    indexes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    /// Checks every pair, which is slow, but obviously right.
+    fn count_inversions_naive<T: Ord>(items: &[T]) -> u64 {
+        let mut inversions = 0;
+        for i in 0..items.len() {
+            for j in i + 1..items.len() {
+                if items[j] < items[i] {
+                    inversions += 1;
+                }
+            }
+        }
+        inversions
+    }
+
+    /// Tries every subsequence, so `items` must be short.
+    fn rem_naive<T: Ord>(items: &[T]) -> usize {
+        let longest = (0u32..1 << items.len())
+            .filter(|mask| {
+                let kept: Vec<&T> = (0..items.len())
+                    .filter(|i| mask & (1 << i) != 0)
+                    .map(|i| &items[i])
+                    .collect();
+                kept.windows(2).all(|w| w[0] <= w[1])
+            })
+            .map(u32::count_ones)
+            .max()
+            .unwrap();
+        items.len() - longest as usize
+    }
+
+    #[test]
+    fn check_measures_on_sorted_items() {
+        for items in [vec![], vec![1], vec![1, 2, 3, 4], vec![1, 1, 2, 2, 2, 3]] {
+            assert_eq!(count_inversions(&items), 0, "{:?}", items);
+            assert_eq!(runs(&items), items.len().min(1), "{:?}", items);
+            assert_eq!(rem(&items), 0, "{:?}", items);
+            assert_eq!(max_displacement(&items), 0, "{:?}", items);
+        }
+    }
+
+    #[test]
+    fn check_measures_on_reversed_items() {
+        let items: Vec<u32> = (0..100).rev().collect();
+        assert_eq!(count_inversions(&items), 100 * 99 / 2);
+        assert_eq!(runs(&items), 100);
+        assert_eq!(rem(&items), 99);
+        assert_eq!(max_displacement(&items), 99);
+
+        // Ties are never out of order
+        let items = [3, 3, 2, 2, 1, 1];
+        assert_eq!(count_inversions(&items), 12);
+        assert_eq!(runs(&items), 3);
+        assert_eq!(rem(&items), 4);
+        assert_eq!(max_displacement(&items), 4);
+    }
+
+    #[test]
+    fn check_count_inversions_matches_naive() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for len in [2, 3, 10, 100, 1_000] {
+            for range in [2, 10, 1_000_000] {
+                let items: Vec<u32> = (0..len).map(|_| rng.gen_range(0, range)).collect();
+                assert_eq!(
+                    count_inversions(&items),
+                    count_inversions_naive(&items),
+                    "{:?}",
+                    items
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn check_rem_matches_naive() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for len in 0..12 {
+            for range in [2, 5, 100] {
+                let items: Vec<u32> = (0..len).map(|_| rng.gen_range(0, range)).collect();
+                assert_eq!(rem(&items), rem_naive(&items), "{:?}", items);
+            }
+        }
+    }
+
+    #[test]
+    fn check_max_displacement() {
+        assert_eq!(max_displacement(&[1, 0, 3, 2, 5, 4]), 1);
+        assert_eq!(max_displacement(&[9, 1, 2, 3, 4, 5]), 5);
+
+        // The tied 2s stay in order, so they move as little as possible
+        assert_eq!(max_displacement(&[2, 1, 2]), 1);
+    }
+
+    #[test]
+    fn check_by_variants_use_the_comparator() {
+        let items = [1, 2, 3, 4, 5];
+        let reversed = |a: &i32, b: &i32| b.cmp(a);
+        assert_eq!(count_inversions_by(&items, reversed), 10);
+        assert_eq!(runs_by(&items, reversed), 5);
+        assert_eq!(rem_by(&items, reversed), 4);
+        assert_eq!(max_displacement_by(&items, reversed), 4);
+    }
+
+    #[test]
+    fn check_kendall_tau_distance() {
+        let a = [1, 2, 3, 4, 5];
+        assert_eq!(kendall_tau_distance(&a, &a), 0);
+        assert_eq!(kendall_tau_distance(&a, &[5, 4, 3, 2, 1]), 10);
+        assert_eq!(kendall_tau_distance(&a, &[2, 1, 3, 5, 4]), 2);
+
+        // It's symmetric, and against sorted items it's just their inversions
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut b: Vec<u32> = (0..200).map(|_| rng.gen_range(0, 50)).collect();
+        let mut c = b.clone();
+        c.shuffle(&mut rng);
+        assert_eq!(kendall_tau_distance(&b, &c), kendall_tau_distance(&c, &b));
+
+        b.sort_unstable();
+        assert_eq!(kendall_tau_distance(&b, &c), count_inversions(&c));
+    }
+
+    #[test]
+    #[should_panic(expected = "orderings have different items")]
+    fn check_kendall_tau_distance_needs_the_same_items() {
+        kendall_tau_distance(&[1, 2, 3], &[1, 2, 4]);
+    }
+}
@@ -1,11 +1,129 @@
-pub fn selection_sort<T: Ord>(mut items: &mut [T]) {
+use core::cmp::Ordering;
+
+use crate::heap;
+use crate::stats::{self, Scratch};
+use crate::trace::Recursion;
+
+mod auto;
+mod counting;
+mod float;
+mod kmerge;
+mod merge;
+mod network;
+mod parallel;
+mod permutation;
+mod radix;
+mod registry;
+mod select;
+mod shell;
+mod string;
+mod tim;
+pub use auto::*;
+pub use counting::*;
+pub use float::*;
+pub use kmerge::*;
+pub use merge::*;
+pub use network::*;
+pub use parallel::*;
+pub use permutation::*;
+pub use radix::*;
+pub use registry::*;
+pub use select::*;
+pub use shell::*;
+pub use string::*;
+pub use tim::*;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// Generates the `_by_key` and `_by_cached_key` variants of a sort from its `_by` variant.
+///
+/// These mirror the shape of `slice::sort_by_key` and `slice::sort_by_cached_key`.
+macro_rules! key_variants {
+    ($(fn $by_key:ident, $by_cached_key:ident => $by:ident;)+) => {
+        $(
+            #[doc = concat!("Sorts `items` by the key `f` extracts from each element, using [`", stringify!($by), "`].")]
+            ///
+            /// The key function is called on every comparison, so it should be cheap.
+            pub fn $by_key<T, K, F>(items: &mut [T], mut f: F)
+            where
+                K: Ord,
+                F: FnMut(&T) -> K,
+            {
+                $by(items, stats::counted(|a: &T, b: &T| f(a).cmp(&f(b))));
+            }
+
+            #[doc = concat!("Sorts `items` by the key `f` extracts from each element, using [`", stringify!($by), "`].")]
+            ///
+            /// The key function is called exactly once per element, and the keys are
+            /// cached while sorting. Prefer this when `f` is expensive.
+            pub fn $by_cached_key<T, K, F>(items: &mut [T], f: F)
+            where
+                K: Ord,
+                F: FnMut(&T) -> K,
+            {
+                sort_by_cached_key_with(items, f, |keys| $by(keys, stats::counted(<(K, usize)>::cmp)));
+            }
+        )+
+    };
+}
+
+/// Shared implementation for every `_by_cached_key` variant.
+///
+/// Each key is paired with the index of its element, and those pairs are sorted
+/// with `sort`. Because indices are unique, the pairs never compare equal, so the
+/// result is the same for stable and unstable sorts. Sorts that only look at
+/// the keys must be stable to keep that property.
+fn sort_by_cached_key_with<T, K, F>(items: &mut [T], f: F, sort: impl FnOnce(&mut [(K, usize)]))
+where
+    F: FnMut(&T) -> K,
+{
+    if items.len() < 2 {
+        return;
+    }
+
+    let mut keys: Vec<(K, usize)> = items
+        .iter()
+        .map(f)
+        .enumerate()
+        .map(|(i, k)| (k, i))
+        .collect();
+    let _scratch = Scratch::new::<(K, usize)>(keys.len());
+    sort(&mut keys);
+
+    // `keys[i].1` now holds the index of the element that belongs in slot `i`.
+    // Walk the permutation and swap everything into place.
+    // Anything that we already placed (before `i`) has been swapped away, so we
+    // follow the chain of indices until we find where it went.
+    for i in 0..items.len() {
+        let mut index = keys[i].1;
+        while index < i {
+            index = keys[index].1;
+        }
+        keys[i].1 = index;
+        stats::swap(items, i, index);
+    }
+}
+
+pub fn selection_sort<T: Ord>(items: &mut [T]) {
+    selection_sort_by(items, T::cmp);
+}
+
+pub fn selection_sort_by<T, F>(mut items: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     // Find the smallest element left in our (shrinking) items
-    while let Some((min, _elem)) = items.iter().enumerate().min_by_key(|(_i, k)| *k) {
+    while let Some((min, _elem)) = items
+        .iter()
+        .enumerate()
+        .min_by(|(_i, a), (_j, b)| compare(a, b))
+    {
         // Place it at the front
         // This is where it belongs in the final sorted list, because it's
         // the smallest element in our list now. Everything smaller is outside
         // of "items", in the part that we lobbed off.
-        items.swap(0, min);
+        stats::swap(items, 0, min);
 
         // and then lob off the freshly sorted item from our list.
         items = &mut items[1..];
@@ -13,6 +131,13 @@ pub fn selection_sort<T: Ord>(mut items: &mut [T]) {
 }
 
 pub fn insertion_sort<T: Ord>(items: &mut [T]) {
+    insertion_sort_by(items, T::cmp);
+}
+
+pub fn insertion_sort_by<T, F>(items: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     // Walk the list, leaving everything on the left sorted.
     // We start with a "sorted" list of 1 element, which is trivially sorted.
     for i in 1..items.len() {
@@ -22,9 +147,9 @@ pub fn insertion_sort<T: Ord>(items: &mut [T]) {
 
         // And then we walk backwards in sorted, until our element is in place
         for j in (1..sorted.len()).rev() {
-            if sorted[j] < sorted[j - 1] {
+            if compare(&sorted[j], &sorted[j - 1]) == Ordering::Less {
                 // If we're not sorted, move it down and continue
-                sorted.swap(j, j - 1);
+                stats::swap(sorted, j, j - 1);
             } else {
                 // If we are sorted, we're done!
                 break;
@@ -33,132 +158,1272 @@ pub fn insertion_sort<T: Ord>(items: &mut [T]) {
     }
 }
 
-pub fn merge_sort<T: Ord + Clone>(items: &mut [T]) {
-    fn merge_helper<T: Ord + Clone>(scratch: &mut Vec<T>, items: &mut [T]) {
-        // If our slice is trivially sorted, we can stop recursing.
-        if items.len() <= 1 {
-            return;
+pub fn binary_insertion_sort<T: Ord>(items: &mut [T]) {
+    binary_insertion_sort_by(items, T::cmp);
+}
+
+/// Insertion sort, but finding each item's slot with a binary search.
+///
+/// That takes `O(N lg N)` comparisons in total, instead of `O(N^2)`. Then the
+/// sorted items after the slot are shifted up one with a single rotate, which
+/// moves each of them once, instead of swapping the new item past them one at
+/// a time. It still moves `O(N^2)` items, but that's cheap next to comparing
+/// strings, and moving a large item once beats swapping it.
+///
+/// This is stable: each item goes after any equal items that are already sorted.
+pub fn binary_insertion_sort_by<T, F>(items: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    for i in 1..items.len() {
+        let (sorted, rest) = items.split_at(i);
+        let slot = sorted.partition_point(|item| compare(item, &rest[0]) != Ordering::Greater);
+
+        if slot < i {
+            items[slot..=i].rotate_right(1);
+            stats::wrote(&items[slot..=i]);
         }
+    }
+}
+
+pub fn merge_sort<T: Ord>(items: &mut [T]) {
+    merge_sort_by(items, T::cmp);
+}
+
+/// Merge sort splits the items in half, sorts each half, and then merges them.
+///
+/// This uses the default [`MergeSort`] options, which need a buffer of `N/2` items.
+/// See there for other trade-offs.
+pub fn merge_sort_by<T, F>(items: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    MergeSort::default().sort_by(items, compare);
+}
+
+pub fn quick_sort<T: Ord>(items: &mut [T]) {
+    quick_sort_by(items, T::cmp);
+}
+
+/// Quicksort works by partitioning, and then recursing.
+///
+/// This uses the default [`QuickSort`] options. See there for the details.
+pub fn quick_sort_by<T, F>(items: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    QuickSort::default().sort_by(items, compare);
+}
+
+/// How `quick_sort` picks the pivot that it partitions around.
+///
+/// A good pivot lands near the middle of the sorted items, so that each
+/// partition splits the problem in half.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PivotStrategy {
+    /// Always use the last item.
+    ///
+    /// This is the textbook choice, but already-sorted items pick the worst
+    /// pivot possible every single time.
+    Last,
+
+    /// Use the median of the first, middle, and last items.
+    #[default]
+    MedianOfThree,
 
-        // 1. Pick a pivot point and split the items into two sub arrays
-        let pivot = items.len() / 2;
-        let (left, right) = items.split_at_mut(pivot);
+    /// Use Tukey's ninther: the median of three medians-of-three, sampled
+    /// evenly across the items. Small slices use `MedianOfThree` instead.
+    Ninther,
 
-        // 2. Recurse to sort the sub arrays as smaller problems
-        merge_helper(scratch, left);
-        scratch.clear();
+    /// Use a random item, from a random number generator seeded with `seed`.
+    ///
+    /// The same seed picks the same pivots, so runs are reproducible.
+    Random { seed: u64 },
+}
+
+/// How `quick_sort` rearranges items around the pivot.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PartitionScheme {
+    /// Split into items less than the pivot, and everything else.
+    ///
+    /// Items equal to the pivot are recursed into again, so lots of duplicates
+    /// make this slow.
+    #[default]
+    Lomuto,
+
+    /// Dijkstra's Dutch national flag partition.
+    ///
+    /// Split into items less than, equal to, and greater than the pivot. The
+    /// equal items are all in their final place, and are not recursed into.
+    ThreeWay,
+
+    /// Edelkamp and Weiß's BlockQuicksort partition.
+    ///
+    /// Like `Lomuto`, this splits into items less than the pivot, and everything
+    /// else. But instead of branching on each comparison, it first compares a
+    /// block of items from each end, and writes down which are on the wrong
+    /// side. Then it swaps those in a batch. There's no branch to mispredict
+    /// while comparing, which is much faster for cheap comparisons on random
+    /// items.
+    Block,
+}
 
-        merge_helper(scratch, right);
-        scratch.clear();
+/// Options for `quick_sort`.
+///
+/// ```
+/// use algos::{PartitionScheme, PivotStrategy, QuickSort};
+///
+/// let mut items = vec![3, 1, 2, 3, 1, 2];
+/// QuickSort {
+///     pivot: PivotStrategy::Random { seed: 42 },
+///     partition: PartitionScheme::ThreeWay,
+/// }
+/// .sort(&mut items);
+/// assert_eq!(items, [1, 1, 2, 2, 3, 3]);
+/// ```
+///
+/// Whatever the options, this is an introsort: if the recursion gets deeper
+/// than `2 * lg(N)`, the pivots are clearly not splitting the items well, and
+/// the remaining slice is heap sorted instead. This guarantees `O(N lg N)` in
+/// the worst case.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuickSort {
+    pub pivot: PivotStrategy,
+    pub partition: PartitionScheme,
+}
+
+impl QuickSort {
+    pub fn sort<T: Ord>(&self, items: &mut [T]) {
+        self.sort_by(items, T::cmp);
+    }
 
-        // 3. Merge the two sorted sub-arrays using our scratch memory
-        for thing in itertools::merge(left, right) {
-            scratch.push(thing.clone());
+    pub fn sort_by<T, F>(&self, items: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut pivots = PivotChooser::new(self.pivot);
+        let depth_limit = 2 * log2(items.len());
+
+        self.quick_helper(items, &mut pivots, &mut compare, depth_limit);
+    }
+
+    pub fn sort_by_key<T, K, F>(&self, items: &mut [T], mut f: F)
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.sort_by(items, |a, b| f(a).cmp(&f(b)));
+    }
+
+    fn quick_helper<T, F>(
+        &self,
+        mut items: &mut [T],
+        pivots: &mut PivotChooser,
+        compare: &mut F,
+        mut depth_limit: usize,
+    ) where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        // Each time around the loop sorts another part of the items, so it's
+        // traced like another call.
+        loop {
+            let _recursion = Recursion::enter(items);
+            if items.len() <= INSERTION_SORT_THRESHOLD {
+                insertion_sort_by(items, &mut *compare);
+                return;
+            }
+
+            // We've recursed too many times - our pivots must be bad.
+            // Fall back to something that doesn't care about pivots.
+            if depth_limit == 0 {
+                heap_sort_by(items, &mut *compare);
+                return;
+            }
+            depth_limit -= 1;
+
+            // Everything in `lo..hi` is already in its final slot, so leave it out
+            // of both halves.
+            let (lo, hi) = self.partition_step(items, pivots, compare);
+            let (left, rest) = items.split_at_mut(lo);
+            let right = &mut rest[hi - lo..];
+
+            // Only recurse into the smaller half, and loop on the larger one.
+            // The smaller half is at most N/2 items, so this keeps our stack
+            // depth at O(lg N) no matter how bad the pivots are.
+            if left.len() < right.len() {
+                self.quick_helper(left, pivots, compare, depth_limit);
+                items = right;
+            } else {
+                self.quick_helper(right, pivots, compare, depth_limit);
+                items = left;
+            }
         }
+    }
 
-        // 4. Replace the old ordering with the new one
-        for (old, new) in items.iter_mut().zip(scratch.iter_mut()) {
-            std::mem::swap(old, new);
+    /// Picks a pivot, and partitions `items` around it.
+    ///
+    /// Returns `lo..hi`, which are the items that are now in their final slots.
+    fn partition_step<T, F>(
+        &self,
+        items: &mut [T],
+        pivots: &mut PivotChooser,
+        compare: &mut F,
+    ) -> (usize, usize)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let pivot = pivots.choose(items, compare);
+        match self.partition {
+            PartitionScheme::Lomuto => {
+                let mid = partition(items, pivot, compare);
+                (mid, mid + 1)
+            }
+            PartitionScheme::ThreeWay => partition_three_way(items, pivot, compare),
+            PartitionScheme::Block => {
+                let mid = partition_in_blocks(items, pivot, compare);
+                (mid, mid + 1)
+            }
         }
     }
+}
 
-    // Re-use the scratch buffer across each recurse.
-    // We can do this because the entire function is single-threaded, so only
-    // a single recurse is using this at once.
-    let mut scratch: Vec<T> = Vec::with_capacity(items.len());
+/// Slices this short are handed off to `insertion_sort` by `quick_sort`.
+///
+/// Insertion sort does very little work per item, so it beats partitioning
+/// and recursing on tiny slices.
+const INSERTION_SORT_THRESHOLD: usize = 16;
 
-    merge_helper(&mut scratch, items);
+/// Slices shorter than this use median-of-three instead of the ninther.
+const NINTHER_THRESHOLD: usize = 128;
+
+/// Picks pivots for one run of `quick_sort`, according to a `PivotStrategy`.
+struct PivotChooser {
+    strategy: PivotStrategy,
+    rng: SmallRng,
 }
 
-pub fn quick_sort<T: Ord>(items: &mut [T]) {
-    /// Quicksort works by partitioning, and then recursing.
+impl PivotChooser {
+    fn new(strategy: PivotStrategy) -> Self {
+        let seed = match strategy {
+            PivotStrategy::Random { seed } => seed,
+            _ => 0,
+        };
+
+        PivotChooser {
+            strategy,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Like `new`, but for the `task`th of several sorts of parts of the same
+    /// items, so that random pivots in each don't follow the same sequence.
+    ///
+    /// Task 0 picks the same pivots as `new`.
+    fn for_task(strategy: PivotStrategy, task: u64) -> Self {
+        let strategy = match strategy {
+            PivotStrategy::Random { seed } => PivotStrategy::Random {
+                seed: seed ^ task.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+            },
+            strategy => strategy,
+        };
 
-    // This helper function picks a pivot point and rearranges `items` so that
-    // the pivot point is moved to the correct slot, everything less is on the
-    // left, and everything greater is on the right.
-    fn partition<T: Ord>(items: &mut [T]) -> usize {
-        let pivot: usize = items.len() - 1;
-        let mut first_high: usize = 0;
+        PivotChooser::new(strategy)
+    }
 
-        for i in 0..items.len() {
-            if items[i] < items[pivot] {
-                items.swap(i, first_high);
-                first_high += 1;
+    /// Returns the index of the pivot to use for `items`, which must not be empty.
+    fn choose<T, F>(&mut self, items: &[T], compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let len = items.len();
+        match self.strategy {
+            PivotStrategy::Last => len - 1,
+            PivotStrategy::MedianOfThree => median_of_three(items, 0, len / 2, len - 1, compare),
+            PivotStrategy::Ninther if len < NINTHER_THRESHOLD => {
+                median_of_three(items, 0, len / 2, len - 1, compare)
             }
+            PivotStrategy::Ninther => {
+                let step = len / 8;
+                let mid = len / 2;
+                let end = len - 1;
+
+                let a = median_of_three(items, 0, step, 2 * step, compare);
+                let b = median_of_three(items, mid - step, mid, mid + step, compare);
+                let c = median_of_three(items, end - 2 * step, end - step, end, compare);
+
+                median_of_three(items, a, b, c, compare)
+            }
+            PivotStrategy::Random { .. } => self.rng.gen_range(0, len),
         }
-        items.swap(pivot, first_high);
+    }
+}
+
+/// Returns whichever of the indices `a`, `b`, and `c` holds the median item.
+fn median_of_three<T, F>(items: &[T], a: usize, b: usize, c: usize, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let less =
+        |compare: &mut F, i: usize, j: usize| compare(&items[i], &items[j]) == Ordering::Less;
 
-        first_high
+    if less(compare, a, b) {
+        if less(compare, b, c) {
+            // a < b < c
+            b
+        } else if less(compare, a, c) {
+            // a < c <= b
+            c
+        } else {
+            // c <= a < b
+            a
+        }
+    } else if less(compare, a, c) {
+        // b <= a < c
+        a
+    } else if less(compare, b, c) {
+        // b < c <= a
+        c
+    } else {
+        // c <= b <= a
+        b
     }
+}
 
-    if items.len() > 1 {
-        let pivot = partition(items);
+/// Rearranges `items` around the item at `pivot`, so that everything less is on
+/// the left, and everything else is on the right.
+///
+/// The pivot is moved to the slot between them, which is its final sorted slot,
+/// and that slot is returned.
+fn partition<T, F>(items: &mut [T], pivot: usize, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Keep the pivot out of the way at the end while we walk the items.
+    let last = items.len() - 1;
+    stats::swap(items, pivot, last);
 
-        let (left, right) = items.split_at_mut(pivot);
-        quick_sort(left);
-        quick_sort(right);
+    let mut first_high: usize = 0;
+    for i in 0..last {
+        if compare(&items[i], &items[last]) == Ordering::Less {
+            stats::swap(items, i, first_high);
+            first_high += 1;
+        }
     }
+    stats::swap(items, last, first_high);
+
+    first_high
+}
+
+/// How many items `partition_in_blocks` compares from each end at once.
+///
+/// The offsets of misplaced items are stored in `u8`s, so this can't be more than 256.
+const PARTITION_BLOCK: usize = 128;
+
+/// The same as `partition`, but comparing items in blocks, without branching.
+///
+/// This is the BlockQuicksort partition, as adapted by the standard library's
+/// pattern-defeating quicksort.
+fn partition_in_blocks<T, F>(items: &mut [T], pivot: usize, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Keep the pivot out of the way at the front while we partition the rest.
+    stats::swap(items, 0, pivot);
+    let (pivot, rest) = items.split_at_mut(1);
+    let pivot = &pivot[0];
+    let mut is_less = |item: &T| compare(item, pivot) == Ordering::Less;
+
+    // Our invariants as we go:
+    //      rest[..l]  <  pivot
+    //      rest[l..r]    haven't been placed yet
+    //      rest[r..]  >= pivot
+    // We compare the next `block_l` items from `l`, and write down the offsets
+    // of those that are >= pivot in `offsets_l[start_l..end_l]`. Likewise, we
+    // look at `block_r` items backwards from `r`, writing down those that are
+    // < pivot. Then we can swap pairs of those until one block runs out.
+    let mut l = 0;
+    let mut block_l = PARTITION_BLOCK;
+    let mut offsets_l = [0_u8; PARTITION_BLOCK];
+    let (mut start_l, mut end_l) = (0, 0);
+
+    let mut r = rest.len();
+    let mut block_r = PARTITION_BLOCK;
+    let mut offsets_r = [0_u8; PARTITION_BLOCK];
+    let (mut start_r, mut end_r) = (0, 0);
+
+    loop {
+        // Near the end, shrink the blocks so that they exactly cover what's left.
+        let is_done = r - l <= 2 * PARTITION_BLOCK;
+        if is_done {
+            // A block with offsets left over is a full block that's already counted.
+            let mut remaining = r - l;
+            if start_l < end_l || start_r < end_r {
+                remaining -= PARTITION_BLOCK;
+            }
+
+            if start_l < end_l {
+                block_r = remaining;
+            } else if start_r < end_r {
+                block_l = remaining;
+            } else {
+                block_l = remaining / 2;
+                block_r = remaining - block_l;
+            }
+        }
+
+        // Adding the comparison as a number, instead of branching on it, is
+        // what makes this fast.
+        if start_l == end_l {
+            start_l = 0;
+            end_l = 0;
+            for i in 0..block_l {
+                offsets_l[end_l] = i as u8;
+                end_l += !is_less(&rest[l + i]) as usize;
+            }
+        }
+
+        if start_r == end_r {
+            start_r = 0;
+            end_r = 0;
+            for i in 0..block_r {
+                offsets_r[end_r] = i as u8;
+                end_r += is_less(&rest[r - 1 - i]) as usize;
+            }
+        }
+
+        // Swap each misplaced item on the left with one on the right.
+        let count = (end_l - start_l).min(end_r - start_r);
+        for k in 0..count {
+            let left = l + offsets_l[start_l + k] as usize;
+            let right = r - 1 - offsets_r[start_r + k] as usize;
+            stats::swap(rest, left, right);
+        }
+        start_l += count;
+        start_r += count;
+
+        if start_l == end_l {
+            l += block_l;
+        }
+        if start_r == end_r {
+            r -= block_r;
+        }
+
+        if is_done {
+            break;
+        }
+    }
+
+    // At most one block still has misplaced items, and it's all that's left
+    // between `l` and `r`. Move them to the other side of it.
+    let mid = if start_l < end_l {
+        while start_l < end_l {
+            end_l -= 1;
+            stats::swap(rest, l + offsets_l[end_l] as usize, r - 1);
+            r -= 1;
+        }
+        r
+    } else {
+        while start_r < end_r {
+            end_r -= 1;
+            stats::swap(rest, l, r - 1 - offsets_r[end_r] as usize);
+            l += 1;
+        }
+        l
+    };
+
+    // Put the pivot between the halves: `items[mid]` is the last item that's less.
+    stats::swap(items, 0, mid);
+    mid
+}
+
+/// Rearranges `items` around the item at `pivot` into three groups: items less
+/// than the pivot, then items equal to it, then items greater than it.
+///
+/// Returns `(lo, hi)` such that `items[lo..hi]` are the items equal to the pivot.
+fn partition_three_way<T, F>(items: &mut [T], pivot: usize, compare: &mut F) -> (usize, usize)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Our invariants as we walk `i` forward:
+    //      items[..lo]   <  pivot
+    //      items[lo..i]  == pivot
+    //      items[i..hi]     haven't been looked at yet
+    //      items[hi..]   >  pivot
+    // `items[lo]` is always equal to the pivot, so we compare against that.
+    stats::swap(items, 0, pivot);
+    let mut lo = 0;
+    let mut i = 1;
+    let mut hi = items.len();
+
+    while i < hi {
+        match compare(&items[i], &items[lo]) {
+            Ordering::Less => {
+                stats::swap(items, lo, i);
+                lo += 1;
+                i += 1;
+            }
+            Ordering::Greater => {
+                hi -= 1;
+                stats::swap(items, i, hi);
+            }
+            Ordering::Equal => {
+                i += 1;
+            }
+        }
+    }
+
+    (lo, hi)
+}
+
+/// Floor of log base 2 of `n`, with `log2(0) == 0`.
+fn log2(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros()).saturating_sub(1) as usize
+}
+
+pub fn heap_sort<T: Ord>(items: &mut [T]) {
+    heap_sort_by(items, T::cmp);
+}
+
+/// Heap sort sorts in place, in `O(N lg N)` time even in the worst case.
+///
+/// The items are arranged into a max heap in place, and then the largest item
+/// is repeatedly swapped to the back of the shrinking heap.
+/// `quick_sort` also uses this when its pivots go bad.
+pub fn heap_sort_by<T, F>(items: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    heap::heapify(items, &mut compare);
+
+    // Pop the max item off of the heap and place it after the heap.
+    for end in (1..items.len()).rev() {
+        stats::swap(items, 0, end);
+        heap::sift_down(&mut items[..end], 0, &mut compare);
+    }
+}
+
+key_variants! {
+    fn selection_sort_by_key, selection_sort_by_cached_key => selection_sort_by;
+    fn insertion_sort_by_key, insertion_sort_by_cached_key => insertion_sort_by;
+    fn binary_insertion_sort_by_key, binary_insertion_sort_by_cached_key => binary_insertion_sort_by;
+    fn merge_sort_by_key, merge_sort_by_cached_key => merge_sort_by;
+    fn quick_sort_by_key, quick_sort_by_cached_key => quick_sort_by;
+    fn heap_sort_by_key, heap_sort_by_cached_key => heap_sort_by;
+    fn tim_sort_by_key, tim_sort_by_cached_key => tim_sort_by;
+    fn shell_sort_by_key, shell_sort_by_cached_key => shell_sort_by;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::cell::{Cell, RefCell};
+    use core::sync::atomic::{self, AtomicUsize};
+    use itertools::Itertools;
     use rand::prelude::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    /// Checks that `sort` sorts lots of different inputs, and panics with `name` if not.
+    fn check_sort(name: &str, sort: &dyn Fn(&mut [i32])) {
+        let sorted: Vec<i32> = (0..10).collect();
+
+        // Empty slice should work
+        let mut v: Vec<i32> = vec![];
+        sort(&mut v);
+        assert_eq!(v, &[], "{}", name);
+
+        // Trivially sorted list with a single element
+        let mut v: Vec<i32> = vec![1];
+        sort(&mut v);
+        assert_eq!(v, &[1], "{}", name);
 
-    macro_rules! make_test {
-        ($(fn $test_name:ident => $sort_fn:expr),+) => {
+        // Sometimes lists of two are too much trouble.
+        let mut v: Vec<i32> = vec![2, 1];
+        sort(&mut v);
+        assert_eq!(v, &[1, 2], "{}", name);
+
+        let mut v: Vec<i32> = (0..10).collect();
+        sort(&mut v);
+        assert_eq!(v, sorted, "{}", name);
+
+        let mut v: Vec<i32> = (0..10).rev().collect();
+        sort(&mut v);
+        assert_eq!(v, sorted, "{}", name);
+
+        let mut v: Vec<i32> = vec![0, 9, 1, 8, 2, 7, 3, 6, 4, 5];
+        sort(&mut v);
+        assert_eq!(v, sorted, "{}", name);
+
+        // Let's make 10 shuffled arrays and sort each one.
+        for _ in 0..10 {
+            let mut v = sorted.clone();
+            v.shuffle(&mut thread_rng());
+
+            sort(&mut v);
+            assert_eq!(v, sorted, "{}", name);
+        }
+
+        // Larger lists take different paths through some sorts,
+        // so shuffle some of those too, with plenty of duplicates.
+        let mut big: Vec<i32> = (0..1_000).map(|i| i / 3).collect();
+        let big_sorted = big.clone();
+        for _ in 0..3 {
+            big.shuffle(&mut thread_rng());
+
+            sort(&mut big);
+            assert_eq!(big, big_sorted, "{}", name);
+        }
+    }
+
+    /// An item that only compares by its `key`, and remembers where it started.
+    #[derive(Copy, Clone, Debug)]
+    struct Tagged {
+        key: usize,
+        index: usize,
+    }
+
+    impl PartialEq for Tagged {
+        fn eq(&self, other: &Self) -> bool {
+            self.key == other.key
+        }
+    }
+
+    impl Eq for Tagged {}
+
+    impl PartialOrd for Tagged {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Tagged {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.key.cmp(&other.key)
+        }
+    }
+
+    /// Checks that `sort` keeps items with equal keys in their original order.
+    fn check_stable_sort(name: &str, sort: &dyn Fn(&mut [Tagged])) {
+        // Lots of duplicate keys, so the indices must end up sorted within each key.
+        let mut v: Vec<Tagged> = (0..1_000)
+            .map(|index| Tagged {
+                key: (index * 7) % 10,
+                index,
+            })
+            .collect();
+        sort(&mut v);
+
+        let v: Vec<(usize, usize)> = v.iter().map(|t| (t.key, t.index)).collect();
+        let mut expected = v.clone();
+        expected.sort();
+        assert_eq!(v, expected, "{}", name);
+    }
+
+    #[test]
+    fn check_sorters() {
+        for sorter in sorters::<i32>() {
+            check_sort(sorter.name(), &|v| sorter.sort(v));
+        }
+
+        for sorter in comparison_sorters::<Tagged>() {
+            if sorter.stable() {
+                check_stable_sort(sorter.name(), &|v| sorter.sort(v));
+            }
+        }
+    }
+
+    #[test]
+    fn check_string_sorters() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let original: Vec<String> = (0..1_000)
+            .map(|_| {
+                let len = rng.gen_range(0, 8);
+                (0..len)
+                    .map(|_| rng.gen_range(b'a', b'd') as char)
+                    .collect()
+            })
+            .collect();
+        let mut expected = original.clone();
+        expected.sort();
+
+        for sorter in comparison_sorters::<String>()
+            .into_iter()
+            .chain(string_sorters())
+        {
+            let mut v = original.clone();
+            sorter.sort(&mut v);
+            assert_eq!(v, expected, "{}", sorter.name());
+        }
+    }
+
+    #[test]
+    fn check_sorters_every_permutation() {
+        // Every order of up to 8 distinct items, and every order of 8 items
+        // that are mostly duplicates.
+        let mut inputs: Vec<Vec<i32>> = vec![];
+        for len in 0..=8 {
+            inputs.extend((0..len).permutations(len as usize));
+        }
+        inputs.extend(
+            [0, 0, 0, 1, 1, 2, 2, 2]
+                .iter()
+                .copied()
+                .permutations(8)
+                .unique(),
+        );
+
+        for sorter in sorters::<i32>() {
+            for input in inputs.iter() {
+                if !sorter.requires().admits(input) {
+                    continue;
+                }
+
+                let mut v = input.clone();
+                sorter.sort(&mut v);
+
+                let mut expected = input.clone();
+                expected.sort_unstable();
+                assert_eq!(v, expected, "{} sorting {:?}", sorter.name(), input);
+            }
+        }
+    }
+
+    #[test]
+    fn check_stable_sorters_every_key_sequence() {
+        // Every sequence of 7 keys out of 3, tagged with where they started.
+        let inputs = (0..7).map(|_| 0..3).multi_cartesian_product();
+
+        let stable: Vec<_> = comparison_sorters::<Tagged>()
+            .into_iter()
+            .filter(|s| s.stable())
+            .collect();
+        for keys in inputs {
+            let input: Vec<Tagged> = keys
+                .iter()
+                .enumerate()
+                .map(|(index, key)| Tagged { key: *key, index })
+                .collect();
+
+            let mut expected: Vec<(usize, usize)> =
+                input.iter().map(|t| (t.key, t.index)).collect();
+            expected.sort_unstable();
+
+            for sorter in stable.iter() {
+                let mut v = input.clone();
+                sorter.sort(&mut v);
+
+                let v: Vec<(usize, usize)> = v.iter().map(|t| (t.key, t.index)).collect();
+                assert_eq!(v, expected, "{} sorting {:?}", sorter.name(), keys);
+            }
+        }
+    }
+
+    thread_local! {
+        /// How many more comparisons `Bomb`s can make before one panics.
+        static FUSE: Cell<usize> = const { Cell::new(usize::MAX) };
+    }
+
+    /// An item that panics when compared, once `FUSE` runs out.
+    #[derive(Copy, Clone, Debug)]
+    struct Bomb {
+        key: i32,
+        id: usize,
+    }
+
+    impl Bomb {
+        /// Makes `len` items with plenty of duplicate keys, and a unique `id` each.
+        fn shuffled(len: usize) -> Vec<Bomb> {
+            let mut rng = SmallRng::seed_from_u64(len as u64);
+            (0..len)
+                .map(|id| Bomb {
+                    key: rng.gen_range(0, len as i32 / 2 + 1),
+                    id,
+                })
+                .collect()
+        }
+
+        /// Runs `sort`, with the `fuse`th comparison panicking.
+        ///
+        /// Returns whether it panicked.
+        fn explode_after(fuse: usize, sort: impl FnOnce()) -> bool {
+            struct Defuse;
+            impl Drop for Defuse {
+                fn drop(&mut self) {
+                    FUSE.with(|f| f.set(usize::MAX));
+                }
+            }
+
+            FUSE.with(|f| f.set(fuse));
+            let _defuse = Defuse;
+            panic::catch_unwind(AssertUnwindSafe(sort)).is_err()
+        }
+    }
+
+    impl PartialEq for Bomb {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl Eq for Bomb {}
+
+    impl PartialOrd for Bomb {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Bomb {
+        fn cmp(&self, other: &Self) -> Ordering {
+            FUSE.with(|fuse| match fuse.get() {
+                // `resume_unwind` skips the panic hook, so this doesn't print anything.
+                0 => panic::resume_unwind(Box::new("boom")),
+                left => fuse.set(left - 1),
+            });
+            self.key.cmp(&other.key)
+        }
+    }
+
+    /// Counts how many comparisons `sort` makes on `items`.
+    fn count_comparisons(items: &[Bomb], sort: impl Fn(&mut [Bomb])) -> usize {
+        let mut v = items.to_vec();
+        let mut fuse = usize::MAX;
+        FUSE.with(|f| {
+            sort(&mut v);
+            fuse = f.get();
+        });
+        usize::MAX - fuse
+    }
+
+    /// Checks that `items` holds every item in `original` exactly once.
+    fn assert_is_permutation(items: &[Bomb], original: &[Bomb], message: &dyn Fn() -> String) {
+        let mut ids: Vec<usize> = items.iter().map(|b| b.id).collect();
+        ids.sort_unstable();
+        let mut expected: Vec<usize> = original.iter().map(|b| b.id).collect();
+        expected.sort_unstable();
+        assert!(ids == expected, "{}", message());
+    }
+
+    #[test]
+    fn check_sorters_panic_safety() {
+        for sorter in comparison_sorters::<Bomb>() {
+            // Panic at every comparison. Quadratic sorts make too many
+            // comparisons on the bigger input to try every one.
+            let lens: &[usize] = match sorter.worst_case() {
+                Complexity::Quadratic => &[10, 40],
+                _ => &[10, 40, 300],
+            };
+
+            for len in lens.iter().copied() {
+                let original = Bomb::shuffled(len);
+                let comparisons = count_comparisons(&original, |v| sorter.sort(v));
+
+                for fuse in 0..comparisons {
+                    let mut v = original.clone();
+                    let panicked = Bomb::explode_after(fuse, || sorter.sort(&mut v));
+
+                    assert!(panicked, "{} didn't panic", sorter.name());
+                    assert_is_permutation(&v, &original, &|| {
+                        format!(
+                            "{} lost or duplicated items after panicking at comparison {} of {} items",
+                            sorter.name(),
+                            fuse,
+                            len
+                        )
+                    });
+                }
+            }
+        }
+    }
+
+    thread_local! {
+        /// How many times each `Droppy` has been dropped, by id.
+        static DROPS: RefCell<Vec<usize>> = const { RefCell::new(vec![]) };
+    }
+
+    /// A `Bomb` that isn't `Copy`, and counts its drops, so that sorts that
+    /// move items with `ptr::read` and `ptr::write` can't quietly duplicate one.
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Droppy(Bomb);
+
+    impl Drop for Droppy {
+        fn drop(&mut self) {
+            DROPS.with(|drops| drops.borrow_mut()[self.0.id] += 1);
+        }
+    }
+
+    /// Sorts `Droppy`s with `sort`, panicking at each comparison in turn, and
+    /// checks that every item is dropped exactly once each time.
+    fn check_drops(name: &str, len: usize, sort: &dyn Fn(&mut [Droppy])) {
+        let original = Bomb::shuffled(len);
+        let make = || original.iter().copied().map(Droppy).collect::<Vec<_>>();
+        DROPS.with(|drops| *drops.borrow_mut() = vec![0; len]);
+
+        let mut v = make();
+        let fuse = FUSE.with(|f| {
+            sort(&mut v);
+            f.get()
+        });
+        drop(v);
+        let comparisons = usize::MAX - fuse;
+
+        for fuse in 0..comparisons {
+            DROPS.with(|drops| *drops.borrow_mut() = vec![0; len]);
+
+            let mut v = make();
+            assert!(
+                Bomb::explode_after(fuse, || sort(&mut v)),
+                "{} didn't panic",
+                name
+            );
+            drop(v);
+
+            let drops = DROPS.with(|drops| drops.borrow().clone());
+            assert!(
+                drops.iter().all(|count| *count == 1),
+                "{} dropped items {:?} times after panicking at comparison {} of {} items",
+                name,
+                drops,
+                fuse,
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn check_panic_safety_drops() {
+        for sorter in comparison_sorters::<Droppy>() {
+            // Long enough for `quick-block` to partition whole blocks
+            let lens: &[usize] = match sorter.worst_case() {
+                Complexity::Quadratic => &[10, 40],
+                _ => &[10, 40, 300],
+            };
+            for len in lens.iter().copied() {
+                check_drops(sorter.name(), len, &|v| sorter.sort(v));
+            }
+        }
+
+        // These aren't sorts, so they aren't in the registry
+        type Sort = fn(&mut [Droppy]);
+        let sorts: &[(&str, Sort)] = &[
+            ("network", |v| SortingNetwork::best(v.len()).sort(v)),
+            ("select-nth", |v| {
+                let middle = v.len() / 2;
+                select_nth(v, middle);
+            }),
+            ("select-nth-median-of-medians", |v| {
+                let middle = v.len() / 2;
+                select_nth_median_of_medians(v, middle);
+            }),
+            ("partial-sort", |v| {
+                let half = v.len() / 2;
+                partial_sort(v, half)
+            }),
+        ];
+        for (name, sort) in sorts.iter() {
+            for len in [10, 32].iter().copied() {
+                check_drops(name, len, sort);
+            }
+        }
+    }
+
+    #[test]
+    fn check_parallel_panic_safety_drops() {
+        // Long enough to be split across threads
+        let len = 20_000;
+        let original = Bomb::shuffled(len);
+        let main = std::thread::current().id();
+
+        type ParSort = fn(&mut [Droppy], &(dyn Fn(&Droppy, &Droppy) -> Ordering + Sync));
+        let sorts: &[(&str, ParSort)] = &[
+            ("par-merge", |v, c| {
+                ParMergeSort { threads: 4 }.sort_by(v, c)
+            }),
+            ("par-quick", |v, c| {
+                ParQuickSort {
+                    threads: 4,
+                    ..ParQuickSort::default()
+                }
+                .sort_by(v, c)
+            }),
+        ];
+        for (name, sort) in sorts.iter() {
+            for fuse in [0, 100, 1_000, 10_000].iter().copied() {
+                DROPS.with(|drops| *drops.borrow_mut() = vec![0; len]);
+                let mut v: Vec<Droppy> = original.iter().copied().map(Droppy).collect();
+
+                // Only count comparisons on the spawned threads, so that the
+                // panic always happens on one of them.
+                let count = AtomicUsize::new(0);
+                let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+                    sort(&mut v, &|a, b| {
+                        if std::thread::current().id() != main
+                            && count.fetch_add(1, atomic::Ordering::Relaxed) == fuse
+                        {
+                            panic::resume_unwind(Box::new("boom"));
+                        }
+                        a.cmp(b)
+                    })
+                }));
+                assert!(panicked.is_err(), "{} didn't panic at {}", name, fuse);
+                drop(v);
+
+                let drops = DROPS.with(|drops| drops.borrow().clone());
+                assert!(
+                    drops.iter().all(|count| *count == 1),
+                    "{} dropped items the wrong number of times after panicking at {}",
+                    name,
+                    fuse
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn check_shell_sort_custom() {
+        check_sort("shell-custom", &|v| {
+            ShellSort {
+                gaps: GapSequence::Custom(&[9, 3, 1]),
+            }
+            .sort(v)
+        });
+    }
+
+    #[test]
+    fn check_quick_sort_ninther_three_way() {
+        check_sort("quick-ninther-3way", &|v| {
+            QuickSort {
+                pivot: PivotStrategy::Ninther,
+                partition: PartitionScheme::ThreeWay,
+            }
+            .sort(v)
+        });
+    }
+
+    #[test]
+    fn check_median_of_three() {
+        let mut compare = i32::cmp;
+        for items in [1, 2, 3].iter().permutations(3) {
+            let items: Vec<i32> = items.into_iter().copied().collect();
+            let median = median_of_three(&items, 0, 1, 2, &mut compare);
+            assert_eq!(items[median], 2, "median of {:?}", items);
+        }
+    }
+
+    #[test]
+    fn check_partition_three_way() {
+        let mut items = vec![2, 3, 1, 2, 3, 2, 1, 2];
+        let (lo, hi) = partition_three_way(&mut items, 0, &mut i32::cmp);
+
+        assert_eq!((lo, hi), (2, 6));
+        assert!(items[..lo].iter().all(|x| *x < 2));
+        assert!(items[lo..hi].iter().all(|x| *x == 2));
+        assert!(items[hi..].iter().all(|x| *x > 2));
+    }
+
+    #[test]
+    fn check_partition_in_blocks() {
+        let mut rng = thread_rng();
+
+        // Sizes around the block boundaries, where the blocks shrink to fit.
+        for len in (1..600).chain([1_000, 10_000]) {
+            for range in [2, len as i32] {
+                let mut items: Vec<i32> = (0..len).map(|_| rng.gen_range(0, range)).collect();
+                let pivot = rng.gen_range(0, len);
+                let value = items[pivot];
+
+                let mut expected = items.clone();
+                expected.sort_unstable();
+
+                let mid = partition_in_blocks(&mut items, pivot, &mut i32::cmp);
+                assert_eq!(items[mid], value, "len {}", len);
+                assert!(items[..mid].iter().all(|x| *x < value), "len {}", len);
+                assert!(items[mid..].iter().all(|x| *x >= value), "len {}", len);
+
+                items.sort_unstable();
+                assert_eq!(items, expected, "len {}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn check_quick_sort_random_is_reproducible() {
+        let count_compares = |seed: u64| {
+            let mut items: Vec<i32> = (0..1_000).rev().collect();
+            let mut compares = 0;
+
+            QuickSort {
+                pivot: PivotStrategy::Random { seed },
+                ..QuickSort::default()
+            }
+            .sort_by(&mut items, |a, b| {
+                compares += 1;
+                a.cmp(b)
+            });
+
+            compares
+        };
+
+        assert_eq!(count_compares(1), count_compares(1));
+        assert_ne!(count_compares(1), count_compares(2));
+    }
+
+    #[test]
+    fn check_quick_sort_three_way_duplicates() {
+        // With all items equal, one three-way partition finishes the job.
+        let mut items = vec![7; 10_000];
+        let mut compares = 0;
+
+        QuickSort {
+            partition: PartitionScheme::ThreeWay,
+            ..QuickSort::default()
+        }
+        .sort_by(&mut items, |a, b| {
+            compares += 1;
+            a.cmp(b)
+        });
+
+        // A few compares to pick the pivot, and one per item to partition.
+        assert!(compares < items.len() + 10, "{} compares", compares);
+    }
+
+    #[test]
+    fn check_log2() {
+        assert_eq!(log2(0), 0);
+        assert_eq!(log2(1), 0);
+        assert_eq!(log2(2), 1);
+        assert_eq!(log2(3), 1);
+        assert_eq!(log2(4), 2);
+        assert_eq!(log2(1 << 20), 20);
+    }
+
+    #[test]
+    fn check_quick_sort_worst_cases() {
+        // Picking the last item as a pivot makes these quadratic, and deep
+        // enough to blow the stack, without the heap sort fallback.
+        let sorted: Vec<i32> = (0..100_000).collect();
+
+        let mut v = sorted.clone();
+        quick_sort(&mut v);
+        assert_eq!(v, sorted);
+
+        let mut v: Vec<i32> = sorted.iter().rev().copied().collect();
+        quick_sort(&mut v);
+        assert_eq!(v, sorted);
+
+        let mut v = vec![7; 100_000];
+        quick_sort(&mut v);
+        assert_eq!(v, vec![7; 100_000]);
+    }
+
+    /// A record that sorts by more than one field, used to check the `_by` variants.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Person {
+        name: &'static str,
+        age: u32,
+    }
+
+    const PEOPLE: &[Person] = &[
+        Person {
+            name: "dave",
+            age: 40,
+        },
+        Person {
+            name: "Alice",
+            age: 30,
+        },
+        Person {
+            name: "carol",
+            age: 25,
+        },
+        Person {
+            name: "Bob",
+            age: 30,
+        },
+        Person {
+            name: "erin",
+            age: 25,
+        },
+    ];
+
+    macro_rules! make_variant_test {
+        ($(fn $test_name:ident => $by:expr, $by_key:expr, $by_cached_key:expr),+) => {
             $(
                 #[test]
                 fn $test_name () {
-                    // Poor man's type checking
-                    let sort: &dyn Fn(&mut [i32]) = &$sort_fn;
-                    let sorted: Vec<i32> = (0..10).collect();
-
-                    // Empty slice should work
-                    let mut v: Vec<i32> = vec![];
-                    sort(&mut v);
-                    assert_eq!(v, &[]);
-
-                    // Trivially sorted list with a single element
-                    let mut v: Vec<i32> = vec![1];
-                    sort(&mut v);
-                    assert_eq!(v, &[1]);
-
-                    // Sometimes lists of two are too much trouble.
-                    let mut v: Vec<i32> = vec![2, 1];
-                    sort(&mut v);
-                    assert_eq!(v, &[1, 2]);
-
-                    let mut v: Vec<i32>  = (0..10).collect();
-                    sort(&mut v);
-                    assert_eq!(v, sorted);
-
-                    let mut v: Vec<i32>  = (0..10).rev().collect();
-                    sort(&mut v);
-                    assert_eq!(v, sorted);
-
-                    let mut v: Vec<i32>  = vec![0, 9, 1, 8, 2, 7, 3, 6, 4, 5];
-                    sort(&mut v);
-                    assert_eq!(v, sorted);
-
-                    // Let's make 10 shuffled arrays and sort each one.
-                    for _ in 0..10 {
-                        let mut v = sorted.clone();
-                        v.shuffle(&mut thread_rng());
-
-                        sort(&mut v);
-                        assert_eq!(v, sorted);
-                    }
+                    let by: &dyn Fn(&mut [Person], &mut dyn FnMut(&Person, &Person) -> Ordering) =
+                        &|v, f| $by(v, f);
+                    let by_key: &dyn Fn(&mut [Person], &mut dyn FnMut(&Person) -> u32) =
+                        &|v, f| $by_key(v, f);
+                    let by_cached_key: &dyn Fn(&mut [Person], &mut dyn FnMut(&Person) -> String) =
+                        &|v, f| $by_cached_key(v, f);
+
+                    // Descending order
+                    let mut v: Vec<i32> = (0..10).collect();
+                    $by(&mut v, |a: &i32, b: &i32| b.cmp(a));
+                    assert_eq!(v, (0..10).rev().collect::<Vec<_>>());
+
+                    // Sort records by a comparator over a field
+                    let mut people = PEOPLE.to_vec();
+                    by(&mut people, &mut |a, b| a.name.cmp(b.name));
+                    let names: Vec<_> = people.iter().map(|p| p.name).collect();
+                    assert_eq!(names, ["Alice", "Bob", "carol", "dave", "erin"]);
+
+                    // Sort records by a key
+                    let mut people = PEOPLE.to_vec();
+                    by_key(&mut people, &mut |p| p.age);
+                    let ages: Vec<_> = people.iter().map(|p| p.age).collect();
+                    assert_eq!(ages, [25, 25, 30, 30, 40]);
+
+                    // Sort records by an expensive, case-insensitive key
+                    let mut calls = 0;
+                    let mut people = PEOPLE.to_vec();
+                    by_cached_key(&mut people, &mut |p| {
+                        calls += 1;
+                        p.name.to_lowercase()
+                    });
+                    let names: Vec<_> = people.iter().map(|p| p.name).collect();
+                    assert_eq!(names, ["Alice", "Bob", "carol", "dave", "erin"]);
+                    assert_eq!(calls, PEOPLE.len());
                 }
             )+
         }
     }
 
-    make_test! {
-        fn check_std_sort => |v| v.sort_unstable(),
-        fn check_selection_sort => selection_sort,
-        fn check_insertion_sort => insertion_sort,
-        fn check_merge_sort => merge_sort,
-        fn check_quick_sort => quick_sort
+    make_variant_test! {
+        fn check_selection_sort_variants =>
+            selection_sort_by, selection_sort_by_key, selection_sort_by_cached_key,
+        fn check_insertion_sort_variants =>
+            insertion_sort_by, insertion_sort_by_key, insertion_sort_by_cached_key,
+        fn check_binary_insertion_sort_variants =>
+            binary_insertion_sort_by, binary_insertion_sort_by_key, binary_insertion_sort_by_cached_key,
+        fn check_merge_sort_variants =>
+            merge_sort_by, merge_sort_by_key, merge_sort_by_cached_key,
+        fn check_quick_sort_variants =>
+            quick_sort_by, quick_sort_by_key, quick_sort_by_cached_key,
+        fn check_heap_sort_variants =>
+            heap_sort_by, heap_sort_by_key, heap_sort_by_cached_key,
+        fn check_tim_sort_variants =>
+            tim_sort_by, tim_sort_by_key, tim_sort_by_cached_key,
+        fn check_shell_sort_variants =>
+            shell_sort_by, shell_sort_by_key, shell_sort_by_cached_key
+    }
+
+    #[test]
+    fn check_stable_sorts_by_key() {
+        // Both sorts below are stable, so people with the same age should keep
+        // their original (by name) order.
+        let expected = ["carol", "erin", "Alice", "Bob", "dave"];
+
+        let mut people = PEOPLE.to_vec();
+        people.sort_by_key(|p| p.name.to_lowercase());
+        insertion_sort_by_key(&mut people, |p| p.age);
+        let names: Vec<_> = people.iter().map(|p| p.name).collect();
+        assert_eq!(names, expected);
+
+        let mut people = PEOPLE.to_vec();
+        people.sort_by_key(|p| p.name.to_lowercase());
+        merge_sort_by_key(&mut people, |p| p.age);
+        let names: Vec<_> = people.iter().map(|p| p.name).collect();
+        assert_eq!(names, expected);
     }
 }
@@ -0,0 +1,443 @@
+use core::any::TypeId;
+use core::cmp::Ordering;
+use core::fmt;
+use core::mem;
+
+use super::*;
+
+/// Slices this short are sorted with `insertion_sort`, without sampling them.
+const SHORT_LEN: usize = 16;
+
+/// Integer keys are radix sorted from this many items. Below that, counting
+/// each byte costs more than comparing.
+const RADIX_MIN_LEN: usize = 64;
+
+/// How many items, and neighbouring pairs, are looked at to guess what the
+/// rest of them are like.
+const SAMPLE_LEN: usize = 64;
+
+/// Picks a sort for `items` by looking at a sample of them, and runs it.
+///
+/// Integers are recognised by their type, which needs `T: 'static`, so this
+/// can't sort items that borrow, like `&str`. `auto_sort_by(items, Ord::cmp)`
+/// sorts those, and picks the same way, except that it never picks `radix`:
+///
+/// ```
+/// use algos::auto_sort_by;
+///
+/// let text = String::from("the quick brown fox");
+/// let mut words: Vec<&str> = text.split(' ').collect();
+/// auto_sort_by(&mut words, Ord::cmp);
+/// assert_eq!(words, ["brown", "fox", "quick", "the"]);
+/// ```
+pub fn auto_sort<T: Ord + 'static>(items: &mut [T]) {
+    AutoSort::default().sort(items);
+}
+
+/// Picks a sort for `items` by looking at a sample of them, and runs it.
+///
+/// This uses the default [`AutoSort`] options. See there for how it picks.
+pub fn auto_sort_by<T, F>(items: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    AutoSort::default().sort_by(items, compare);
+}
+
+/// Options for `auto_sort`.
+///
+/// This looks at how many items there are, and at a sample of up to 64 of
+/// them and their neighbours, then picks the first sort that fits:
+///
+/// | Items                                   | Sort         | Because                               |
+/// |-----------------------------------------|--------------|---------------------------------------|
+/// | Fewer than 16                           | `insertion`  | it has the least overhead             |
+/// | Sampled pairs nearly all in order       | `tim`        | it finds the runs, and merges them    |
+/// | Sampled pairs nearly all out of order   | `tim`        | it reverses descending runs           |
+/// | Integers, at least 64 of them           | `radix`      | it's linear, and never compares       |
+/// | A quarter or fewer sampled are distinct | `quick-3way` | it groups equal items, and skips them |
+/// | Anything else                           | `quick`      | it's the fastest on random items      |
+///
+/// The names are the ones that [`sorters`] lists them under. `quick-3way`
+/// and `quick` aren't stable, so when `stable` is set, `merge` is used instead.
+///
+/// Sampling compares up to 64 neighbouring pairs, and sorts up to 64 items, so
+/// it takes about the same time however many items there are. Integers are
+/// recognised by their type, so only `sort` and `explain` pick `radix`, and
+/// they need `T: 'static`. A comparator could put them in any order.
+///
+/// [`explain`](AutoSort::explain) shows what would be picked, and why, without
+/// sorting anything. `sort` returns the same thing:
+///
+/// ```
+/// use algos::{AutoSort, Reason};
+///
+/// let mut items: Vec<u32> = (0..1_000).collect();
+/// items.swap(10, 20);
+///
+/// let choice = AutoSort::default().sort(&mut items);
+/// assert_eq!(choice.sorter, "tim");
+/// assert_eq!(choice.reason, Reason::Presorted);
+/// assert_eq!(
+///     choice.to_string(),
+///     "tim for 1000 items: 0 of 64 sampled pairs were out of order, so they're probably mostly sorted"
+/// );
+/// ```
+///
+/// This never starts any threads. Use `par_merge_sort` or `par_quick_sort`
+/// for that.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AutoSort {
+    /// Only pick stable sorts, which keep equal items in their original order.
+    pub stable: bool,
+}
+
+impl AutoSort {
+    /// Sorts `items`, and returns which sort it picked and why.
+    pub fn sort<T: Ord + 'static>(&self, items: &mut [T]) -> Choice {
+        let choice = self.explain(items);
+        match choice.reason {
+            Reason::IntegerKeys => {
+                let radix_sort = integer_radix_sort::<T>().expect("checked by `explain`");
+                radix_sort(items);
+            }
+            _ => Self::run(choice, items, T::cmp),
+        }
+        choice
+    }
+
+    /// Sorts `items` with `compare`, and returns which sort it picked and why.
+    ///
+    /// This never picks `radix`, since `compare` might not put integers in
+    /// their usual order.
+    pub fn sort_by<T, F>(&self, items: &mut [T], mut compare: F) -> Choice
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let choice = self.explain_by(items, &mut compare);
+        Self::run(choice, items, compare);
+        choice
+    }
+
+    pub fn sort_by_key<T, K, F>(&self, items: &mut [T], mut f: F) -> Choice
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.sort_by(items, |a, b| f(a).cmp(&f(b)))
+    }
+
+    /// Returns which sort `sort` would pick for `items`, and why.
+    pub fn explain<T: Ord + 'static>(&self, items: &[T]) -> Choice {
+        let integer_keys = integer_radix_sort::<T>().is_some();
+        self.choose(Sample::new(items, T::cmp, integer_keys))
+    }
+
+    /// Returns which sort `sort_by` would pick for `items`, and why.
+    pub fn explain_by<T, F>(&self, items: &[T], compare: F) -> Choice
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.choose(Sample::new(items, compare, false))
+    }
+
+    fn choose(&self, sample: Sample) -> Choice {
+        let (sorter, reason) = if sample.len < SHORT_LEN {
+            ("insertion", Reason::Short)
+        } else if sample.descents * 16 <= sample.pairs {
+            ("tim", Reason::Presorted)
+        } else if sample.descents * 16 >= sample.pairs * 15 {
+            ("tim", Reason::Reversed)
+        } else if sample.integer_keys && sample.len >= RADIX_MIN_LEN {
+            ("radix", Reason::IntegerKeys)
+        } else if self.stable {
+            ("merge", Reason::Stable)
+        } else if sample.distinct * 4 <= sample.sampled {
+            ("quick-3way", Reason::FewDistinct)
+        } else {
+            ("quick", Reason::NoPattern)
+        };
+
+        Choice {
+            sorter,
+            reason,
+            sample,
+        }
+    }
+
+    /// Runs the comparison sort that `choice` picked.
+    fn run<T, F>(choice: Choice, items: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        match choice.sorter {
+            "insertion" => insertion_sort_by(items, compare),
+            "tim" => tim_sort_by(items, compare),
+            "merge" => merge_sort_by(items, compare),
+            "quick-3way" => QuickSort {
+                partition: PartitionScheme::ThreeWay,
+                ..QuickSort::default()
+            }
+            .sort_by(items, compare),
+            "quick" => quick_sort_by(items, compare),
+            sorter => unreachable!("{} isn't a comparison sort", sorter),
+        }
+    }
+}
+
+/// Which sort [`AutoSort`] picked, and why.
+///
+/// Its `Display` explains the choice in a sentence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Choice {
+    /// The name of the sort, as [`sorters`] lists it.
+    pub sorter: &'static str,
+
+    /// Why it was picked.
+    pub reason: Reason,
+
+    /// What the sample showed about the items.
+    pub sample: Sample,
+}
+
+impl fmt::Display for Choice {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let sample = &self.sample;
+        write!(fmt, "{} for {} items: ", self.sorter, sample.len)?;
+        match self.reason {
+            Reason::Short => write!(fmt, "there are too few to be worth sampling"),
+            Reason::Presorted => write!(
+                fmt,
+                "{} of {} sampled pairs were out of order, so they're probably mostly sorted",
+                sample.descents, sample.pairs
+            ),
+            Reason::Reversed => write!(
+                fmt,
+                "{} of {} sampled pairs were out of order, so they're probably mostly reversed",
+                sample.descents, sample.pairs
+            ),
+            Reason::IntegerKeys => write!(fmt, "they're integers, so they can be radix sorted"),
+            Reason::Stable => write!(
+                fmt,
+                "there's no pattern to them, and equal items must keep their order"
+            ),
+            Reason::FewDistinct => write!(
+                fmt,
+                "{} of {} sampled items were distinct, so there are lots of duplicates",
+                sample.distinct, sample.sampled
+            ),
+            Reason::NoPattern => write!(
+                fmt,
+                "{} of {} sampled pairs were out of order, and {} of {} sampled items were distinct",
+                sample.descents, sample.pairs, sample.distinct, sample.sampled
+            ),
+        }
+    }
+}
+
+/// Why [`AutoSort`] picked a sort.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// There are too few items to be worth sampling.
+    Short,
+
+    /// Nearly every sampled pair of neighbours was in order.
+    Presorted,
+
+    /// Nearly every sampled pair of neighbours was out of order.
+    Reversed,
+
+    /// The items are a primitive integer type.
+    IntegerKeys,
+
+    /// There was no pattern worth using, and the sort must be stable.
+    Stable,
+
+    /// Many of the sampled items were equal.
+    FewDistinct,
+
+    /// There was no pattern worth using.
+    NoPattern,
+}
+
+/// What [`AutoSort`] learned about some items from a sample of them.
+///
+/// Samples are spread evenly across the items. Short slices are not sampled,
+/// and every count is zero.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sample {
+    /// How many items there are.
+    pub len: usize,
+
+    /// How many pairs of neighbours were compared.
+    pub pairs: usize,
+
+    /// How many of those pairs were out of order.
+    pub descents: usize,
+
+    /// How many items were sampled for duplicates.
+    pub sampled: usize,
+
+    /// How many of those were distinct.
+    pub distinct: usize,
+
+    /// Whether the items are a primitive integer type.
+    pub integer_keys: bool,
+}
+
+impl Sample {
+    fn new<T, F>(items: &[T], mut compare: F, integer_keys: bool) -> Self
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let len = items.len();
+        let mut sample = Sample {
+            len,
+            integer_keys,
+            ..Sample::default()
+        };
+        if len < SHORT_LEN {
+            return sample;
+        }
+
+        let step = |i: usize, of: usize| i * of / SAMPLE_LEN.min(of);
+
+        sample.pairs = SAMPLE_LEN.min(len - 1);
+        sample.descents = (0..sample.pairs)
+            .map(|i| step(i, len - 1))
+            .filter(|&i| compare(&items[i + 1], &items[i]) == Ordering::Less)
+            .count();
+
+        let mut sampled: Vec<&T> = (0..SAMPLE_LEN.min(len))
+            .map(|i| &items[step(i, len)])
+            .collect();
+        insertion_sort_by(&mut sampled, |a, b| compare(a, b));
+        sample.sampled = sampled.len();
+        sample.distinct = 1 + sampled
+            .windows(2)
+            .filter(|w| compare(w[0], w[1]) != Ordering::Equal)
+            .count();
+
+        sample
+    }
+}
+
+/// Returns `radix_sort` for `T`, if `T` is a primitive integer type.
+fn integer_radix_sort<T: 'static>() -> Option<fn(&mut [T])> {
+    macro_rules! integers {
+        ($($ty:ty),+) => {
+            $(
+                if TypeId::of::<T>() == TypeId::of::<$ty>() {
+                    let sort: fn(&mut [$ty]) = radix_sort::<$ty>;
+                    // SAFETY: `T` is `$ty`, so these are the same type
+                    return Some(unsafe { mem::transmute::<fn(&mut [$ty]), fn(&mut [T])>(sort) });
+                }
+            )+
+        };
+    }
+
+    integers!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    /// Inputs that should each be sorted a different way, and how.
+    fn inputs() -> Vec<(Vec<i64>, Reason)> {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut nearly_sorted: Vec<i64> = (0..10_000).collect();
+        for _ in 0..10 {
+            let (i, j) = (rng.gen_range(0, 10_000), rng.gen_range(0, 10_000));
+            nearly_sorted.swap(i, j);
+        }
+
+        vec![
+            (vec![3, 1, 2], Reason::Short),
+            (nearly_sorted.clone(), Reason::Presorted),
+            (nearly_sorted.into_iter().rev().collect(), Reason::Reversed),
+            (
+                (0..10_000).map(|_| rng.gen_range(0, 1_000_000)).collect(),
+                Reason::NoPattern,
+            ),
+            (
+                (0..10_000).map(|_| rng.gen_range(0, 5)).collect(),
+                Reason::FewDistinct,
+            ),
+        ]
+    }
+
+    #[test]
+    fn check_auto_sort_picks_by_sample() {
+        for (items, reason) in inputs() {
+            // Through a comparator, integers are just like anything else
+            let mut sorted = items.clone();
+            let choice = AutoSort::default().sort_by(&mut sorted, i64::cmp);
+            assert_eq!(choice.reason, reason, "{}", choice);
+            assert!(sorted.windows(2).all(|w| w[0] <= w[1]), "{}", choice);
+
+            // Otherwise, anything long enough with no pattern is radix sorted
+            let mut sorted = items.clone();
+            let choice = AutoSort::default().sort(&mut sorted);
+            let reason = match reason {
+                Reason::NoPattern | Reason::FewDistinct => Reason::IntegerKeys,
+                reason => reason,
+            };
+            assert_eq!(choice.reason, reason, "{}", choice);
+            assert!(sorted.windows(2).all(|w| w[0] <= w[1]), "{}", choice);
+        }
+    }
+
+    #[test]
+    fn check_auto_sort_picks_registered_sorts() {
+        let sorters = sorters::<i64>();
+        for (items, _) in inputs() {
+            for stable in [false, true] {
+                let auto = AutoSort { stable };
+                for choice in [auto.explain(&items), auto.explain_by(&items, i64::cmp)] {
+                    let sorter = sorters.iter().find(|s| s.name() == choice.sorter);
+                    let sorter = sorter.unwrap_or_else(|| panic!("{} isn't registered", choice));
+                    assert!(sorter.stable() || !stable, "{}", choice);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_auto_sort_stable() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut items: Vec<(u8, usize)> = (0..1_000).map(|i| (rng.gen_range(0, 4), i)).collect();
+        let mut expected = items.clone();
+        expected.sort_by_key(|(k, _)| *k);
+
+        let choice = AutoSort { stable: true }.sort_by_key(&mut items, |(k, _)| *k);
+        assert_eq!(choice.sorter, "merge");
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn check_auto_sort_explains() {
+        let items: Vec<String> = (0..100).map(|i| format!("{:03}", i % 7)).collect();
+        let choice = AutoSort::default().explain(&items);
+        assert_eq!(
+            choice.to_string(),
+            "quick-3way for 100 items: 7 of 64 sampled items were distinct, so there are lots of duplicates"
+        );
+        assert_eq!(
+            choice.sample,
+            Sample {
+                len: 100,
+                pairs: 64,
+                descents: 10,
+                sampled: 64,
+                distinct: 7,
+                integer_keys: false,
+            }
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use core::cmp::Ordering;
+use core::ops::Range;
+
+use crate::stats::{self, Scratch};
+
+/// Stably sorts `items` by a small integer key, in `O(N + K)` time, where `K` is
+/// the length of `range`.
+///
+/// This is a counting sort. It never compares items: instead it counts how many
+/// items have each key, which tells it exactly where each item belongs.
+/// The key function is called exactly once per element.
+///
+/// ```
+/// use algos::counting_sort_by_key;
+///
+/// let mut people = vec![("carol", 25), ("alice", 30), ("bob", 25)];
+/// counting_sort_by_key(&mut people, 0..120, |(_name, age)| *age);
+/// assert_eq!(people, [("carol", 25), ("bob", 25), ("alice", 30)]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if a key is outside of `range`.
+pub fn counting_sort_by_key<T, F>(items: &mut [T], range: Range<usize>, f: F)
+where
+    F: FnMut(&T) -> usize,
+{
+    super::sort_by_cached_key_with(items, f, |keys| {
+        for (k, _i) in keys.iter() {
+            assert!(
+                range.contains(k),
+                "counting_sort_by_key() found key {} outside of {:?}",
+                k,
+                range
+            );
+        }
+
+        counting_sort_pairs(keys, range.len(), |k| k - range.start);
+    });
+}
+
+/// Stably sorts `pairs` into `buckets` buckets, by `bucket_of` their keys.
+fn counting_sort_pairs<K, F>(pairs: &mut [(K, usize)], buckets: usize, bucket_of: F)
+where
+    K: Copy,
+    F: Fn(&K) -> usize,
+{
+    // Count how many of each key we have, and turn that into the starting
+    // offset of each key in the sorted output.
+    let mut offsets = vec![0_usize; buckets + 1];
+    let _offsets_scratch = Scratch::new::<usize>(offsets.len());
+    for (k, _i) in pairs.iter() {
+        offsets[bucket_of(k) + 1] += 1;
+    }
+    for b in 1..offsets.len() {
+        offsets[b] += offsets[b - 1];
+    }
+
+    // Walking the items in order and appending to buckets keeps this stable.
+    let scratch: Vec<(K, usize)> = pairs.to_vec();
+    let _scratch = Scratch::new::<(K, usize)>(scratch.len());
+    stats::writes(scratch.len());
+    for pair in scratch.iter() {
+        let b = bucket_of(&pair.0);
+        pairs[offsets[b]] = *pair;
+        offsets[b] += 1;
+    }
+    stats::writes(pairs.len());
+}
+
+/// Stably sorts floats, which should be spread uniformly over their range, in
+/// `O(N)` expected time.
+///
+/// See `bucket_sort_by_key`.
+pub fn bucket_sort(items: &mut [f64]) {
+    bucket_sort_by_key(items, |x| *x);
+}
+
+/// Stably sorts `items` by a float key, which should be spread uniformly over
+/// its range, in `O(N)` expected time.
+///
+/// The range between the smallest and largest keys is split into `N` equally
+/// sized buckets, and each item is dropped into the bucket its key falls in.
+/// With uniform keys, each bucket gets about one item, so sorting the buckets
+/// is cheap. Lumpy keys put many items into a few buckets, and those are sorted
+/// with `insertion_sort` in `O(N^2)` time.
+///
+/// The key function is called exactly once per element.
+///
+/// # Panics
+///
+/// Panics if a key is NaN.
+pub fn bucket_sort_by_key<T, F>(items: &mut [T], f: F)
+where
+    F: FnMut(&T) -> f64,
+{
+    super::sort_by_cached_key_with(items, f, |keys| {
+        assert!(
+            keys.iter().all(|(k, _i)| !k.is_nan()),
+            "bucket_sort_by_key() cannot sort NaN keys"
+        );
+
+        let min = keys.iter().map(|(k, _i)| *k).fold(f64::INFINITY, f64::min);
+        let max = keys
+            .iter()
+            .map(|(k, _i)| *k)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let buckets = keys.len();
+
+        // Map a key onto a bucket index in `0..buckets`.
+        // Infinite ranges produce NaN here, which `as` turns into 0.
+        let scale = buckets as f64 / (max - min);
+        let bucket_of = |k: &f64| (((k - min) * scale) as usize).min(buckets - 1);
+
+        counting_sort_pairs(keys, buckets, bucket_of);
+
+        // Each bucket still needs sorting. These should be tiny, and insertion
+        // sort is stable, so we keep the order that the buckets were filled in.
+        let mut start = 0;
+        while start < keys.len() {
+            let b = bucket_of(&keys[start].0);
+            let len = keys[start..]
+                .iter()
+                .take_while(|(k, _i)| bucket_of(k) == b)
+                .count();
+
+            super::insertion_sort_by(
+                &mut keys[start..start + len],
+                stats::counted(|a: &(f64, usize), b: &(f64, usize)| {
+                    a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal)
+                }),
+            );
+            start += len;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    enum Suit {
+        Clubs,
+        Diamonds,
+        Hearts,
+        Spades,
+    }
+
+    #[test]
+    fn check_counting_sort_enum_discriminants() {
+        use Suit::*;
+
+        let mut cards = vec![
+            (Spades, 1),
+            (Hearts, 2),
+            (Clubs, 3),
+            (Spades, 4),
+            (Diamonds, 5),
+            (Clubs, 6),
+        ];
+        counting_sort_by_key(&mut cards, 0..4, |(suit, _rank)| *suit as usize);
+
+        assert_eq!(
+            cards,
+            [
+                (Clubs, 3),
+                (Clubs, 6),
+                (Diamonds, 5),
+                (Hearts, 2),
+                (Spades, 1),
+                (Spades, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_counting_sort_offset_range() {
+        let mut items: Vec<usize> = vec![105, 100, 109, 100, 103];
+        counting_sort_by_key(&mut items, 100..110, |x| *x);
+        assert_eq!(items, [100, 100, 103, 105, 109]);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of 0..4")]
+    fn check_counting_sort_key_out_of_range() {
+        let mut items = vec![1, 2, 4];
+        counting_sort_by_key(&mut items, 0..4, |x| *x);
+    }
+
+    #[test]
+    fn check_bucket_sort_uniform() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut items: Vec<f64> = (0..1_000).map(|_| rng.gen_range(-50.0, 50.0)).collect();
+        let mut expected = items.clone();
+        expected.sort_by(f64::total_cmp);
+
+        bucket_sort(&mut items);
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn check_bucket_sort_lumpy() {
+        let mut items = vec![1e9, 0.5, 0.25, f64::INFINITY, 0.75, -1e9, 0.0, 0.5];
+        bucket_sort(&mut items);
+        assert_eq!(items, [-1e9, 0.0, 0.25, 0.5, 0.5, 0.75, 1e9, f64::INFINITY]);
+
+        let mut items = vec![2.0; 10];
+        bucket_sort(&mut items);
+        assert_eq!(items, vec![2.0; 10]);
+    }
+
+    #[test]
+    fn check_bucket_sort_by_key_is_stable() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut items: Vec<(u32, usize)> = (0..1_000).map(|i| (rng.gen_range(0, 50), i)).collect();
+        let mut expected = items.clone();
+        expected.sort_by_key(|(key, _)| *key);
+
+        bucket_sort_by_key(&mut items, |(key, _)| *key as f64);
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "NaN")]
+    fn check_bucket_sort_nan() {
+        let mut items = vec![1.0, f64::NAN, 0.0];
+        bucket_sort(&mut items);
+    }
+}
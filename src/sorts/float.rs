@@ -0,0 +1,318 @@
+use core::cmp::Ordering;
+use core::fmt;
+
+use super::{radix_sort, RadixKey};
+use crate::stats;
+
+/// `f32` or `f64`, which only have a partial order, because of NaN.
+pub trait Float: RadixKey + PartialOrd {
+    fn is_nan(self) -> bool;
+
+    /// Compares by the IEEE 754 totalOrder, which is the same order as `RadixKey`.
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+
+macro_rules! impl_float {
+    ($($ty:ty)+) => {
+        $(
+            impl Float for $ty {
+                #[inline]
+                fn is_nan(self) -> bool {
+                    <$ty>::is_nan(self)
+                }
+
+                #[inline]
+                fn total_cmp(&self, other: &Self) -> Ordering {
+                    <$ty>::total_cmp(self, other)
+                }
+            }
+        )+
+    };
+}
+
+impl_float! { f32 f64 }
+
+/// A float that's `Ord`, using the IEEE 754 totalOrder:
+///
+/// `-NaN < -inf < ... < -0.0 < 0.0 < ... < inf < NaN`
+///
+/// This lets any sort in this crate sort floats, including by key:
+///
+/// ```
+/// use algos::{merge_sort_by_key, TotalOrder};
+///
+/// let mut points = vec![(1.5, "b"), (-0.0, "a"), (f64::INFINITY, "c")];
+/// merge_sort_by_key(&mut points, |(x, _)| TotalOrder(*x));
+/// assert_eq!(points[0].1, "a");
+/// ```
+///
+/// NaNs can have either sign, so they end up at both ends. Use `sort_floats`
+/// to put them in one place.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TotalOrder<F>(pub F);
+
+impl<F: Float> TotalOrder<F> {
+    /// Views floats as `TotalOrder`s, without copying them.
+    pub fn from_slice_mut(items: &mut [F]) -> &mut [TotalOrder<F>] {
+        // SAFETY: `TotalOrder` is `repr(transparent)`, so it has the same layout as `F`.
+        unsafe { &mut *(items as *mut [F] as *mut [TotalOrder<F>]) }
+    }
+}
+
+impl<F: Float> PartialEq for TotalOrder<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<F: Float> Eq for TotalOrder<F> {}
+
+impl<F: Float> PartialOrd for TotalOrder<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<F: Float> Ord for TotalOrder<F> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl<F: Float> RadixKey for TotalOrder<F> {
+    const BYTES: usize = F::BYTES;
+
+    #[inline]
+    fn radix_byte(&self, byte: usize) -> u8 {
+        self.0.radix_byte(byte)
+    }
+}
+
+/// Where `sort_floats` puts NaNs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NanPolicy {
+    /// Put every NaN before every number.
+    First,
+
+    /// Put every NaN after every number.
+    #[default]
+    Last,
+
+    /// Don't sort at all if there are any NaNs, and return a [`NanError`].
+    Error,
+}
+
+/// `sort_floats` found a NaN, with `NanPolicy::Error`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NanError {
+    /// Where the first NaN is. The items haven't been moved.
+    pub index: usize,
+}
+
+impl fmt::Display for NanError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "can't sort floats with a NaN at index {}", self.index)
+    }
+}
+
+impl std::error::Error for NanError {}
+
+/// Sorts floats by the IEEE 754 totalOrder, with NaNs where `nans` says.
+///
+/// This uses `radix_sort`. See `sort_floats_with` to use a different sort.
+///
+/// ```
+/// use algos::{sort_floats, NanPolicy};
+///
+/// let mut items = [2.5, f64::NAN, -1.0, 0.0, -0.0, f64::NEG_INFINITY];
+/// sort_floats(&mut items, NanPolicy::First).unwrap();
+/// assert!(items[0].is_nan());
+/// assert_eq!(&items[1..], [f64::NEG_INFINITY, -1.0, -0.0, 0.0, 2.5]);
+///
+/// let error = sort_floats(&mut [1.0, f64::NAN], NanPolicy::Error).unwrap_err();
+/// assert_eq!(error.index, 1);
+/// ```
+pub fn sort_floats<F: Float>(items: &mut [F], nans: NanPolicy) -> Result<(), NanError> {
+    sort_floats_with(items, nans, radix_sort)
+}
+
+/// Sorts floats by the IEEE 754 totalOrder, with NaNs where `nans` says, using
+/// `sort` to do the sorting.
+///
+/// The NaNs are moved to one end first, and then `sort` is called on the
+/// numbers, and the NaNs, separately. Without NaNs, the totalOrder is the usual
+/// order, except that `-0.0 < 0.0`. The NaNs are sorted by sign, then payload.
+///
+/// Floats that compare equal in the totalOrder have the same bits, so it
+/// doesn't matter whether `sort` is stable.
+///
+/// ```
+/// use algos::{sort_floats_with, sorters, NanPolicy, Sorter, TotalOrder};
+///
+/// let sorter = &sorters::<TotalOrder<f32>>()[0];
+/// let mut items = [3.0_f32, f32::NAN, 1.0];
+/// sort_floats_with(&mut items, NanPolicy::Last, |v| sorter.sort(v)).unwrap();
+/// assert_eq!(items[..2], [1.0, 3.0]);
+/// ```
+pub fn sort_floats_with<F, S>(items: &mut [F], nans: NanPolicy, mut sort: S) -> Result<(), NanError>
+where
+    F: Float,
+    S: FnMut(&mut [TotalOrder<F>]),
+{
+    let len = items.len();
+    let (numbers, nans) = match nans {
+        NanPolicy::Error => {
+            if let Some(index) = items.iter().position(|x| x.is_nan()) {
+                return Err(NanError { index });
+            }
+            (0..len, len..len)
+        }
+        NanPolicy::First => {
+            let split = partition_nans(items, true);
+            (split..len, 0..split)
+        }
+        NanPolicy::Last => {
+            let split = partition_nans(items, false);
+            (0..split, split..len)
+        }
+    };
+
+    sort(TotalOrder::from_slice_mut(&mut items[numbers]));
+    sort(TotalOrder::from_slice_mut(&mut items[nans]));
+    Ok(())
+}
+
+/// Moves every NaN to the front if `first`, or every number to the front if not.
+///
+/// Returns how many items were moved to the front.
+fn partition_nans<F: Float>(items: &mut [F], first: bool) -> usize {
+    // Everything before `split` has been moved to the front.
+    let mut split = 0;
+    for i in 0..items.len() {
+        if items[i].is_nan() == first {
+            stats::swap(items, split, i);
+            split += 1;
+        }
+    }
+
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sorters, Sorter};
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    /// Floats with every kind of edge case, and lots of ordinary ones.
+    fn floats() -> Vec<f64> {
+        let mut rng = SmallRng::seed_from_u64(0x5EED);
+        let mut items = vec![
+            0.0,
+            -0.0,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::MIN_POSITIVE,
+            -f64::MIN_POSITIVE / 2.0,
+            f64::MAX,
+            f64::MIN,
+            f64::NAN,
+            -f64::NAN,
+            f64::from_bits(f64::NAN.to_bits() | 1),
+            1.0,
+            1.0,
+        ];
+        items.extend((0..200).map(|_| rng.gen_range(-100.0, 100.0)));
+        items.shuffle(&mut rng);
+        items
+    }
+
+    /// Checks `items` are sorted by totalOrder, with the NaNs all at one end.
+    fn check_sorted(name: &str, items: &[f64], original: &[f64], nans: NanPolicy) {
+        let count = original.iter().filter(|x| x.is_nan()).count();
+        let (nan_items, numbers) = match nans {
+            NanPolicy::First => items.split_at(count),
+            _ => {
+                let (numbers, nan_items) = items.split_at(items.len() - count);
+                (nan_items, numbers)
+            }
+        };
+
+        assert!(nan_items.iter().all(|x| x.is_nan()), "{} {:?}", name, items);
+        for part in [numbers, nan_items] {
+            assert!(
+                part.windows(2)
+                    .all(|w| w[0].total_cmp(&w[1]) != Ordering::Greater),
+                "{} {:?}",
+                name,
+                items
+            );
+        }
+
+        let bits = |items: &[f64]| {
+            let mut bits: Vec<u64> = items.iter().map(|x| x.to_bits()).collect();
+            bits.sort_unstable();
+            bits
+        };
+        assert_eq!(bits(items), bits(original), "{}", name);
+    }
+
+    #[test]
+    fn check_every_sorter() {
+        let original = floats();
+        let totals: Vec<_> = original.iter().copied().map(TotalOrder).collect();
+        for sorter in sorters::<TotalOrder<f64>>() {
+            // Floats span far too many keys to count, and don't fit in an
+            // `f64` as integers.
+            if !sorter.requires().admits(&totals) {
+                continue;
+            }
+
+            for nans in [NanPolicy::First, NanPolicy::Last] {
+                let mut items = original.clone();
+                sort_floats_with(&mut items, nans, |v| sorter.sort(v)).unwrap();
+                check_sorted(sorter.name(), &items, &original, nans);
+            }
+        }
+    }
+
+    #[test]
+    fn check_sort_floats() {
+        let original = floats();
+        for nans in [NanPolicy::First, NanPolicy::Last] {
+            let mut items = original.clone();
+            sort_floats(&mut items, nans).unwrap();
+            check_sorted("radix", &items, &original, nans);
+        }
+
+        let mut items = [1.5_f32, -0.0, 0.0, -2.0, f32::NAN];
+        sort_floats(&mut items, NanPolicy::First).unwrap();
+        assert!(items[0].is_nan());
+        assert_eq!(
+            items[1..].iter().map(|x| x.to_bits()).collect::<Vec<_>>(),
+            [-2.0_f32, -0.0, 0.0, 1.5]
+                .iter()
+                .map(|x| x.to_bits())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn check_nan_error() {
+        let mut items = [3.0, 1.0, f64::NAN, 2.0, f64::NAN];
+        let error = sort_floats(&mut items, NanPolicy::Error).unwrap_err();
+        assert_eq!(error, NanError { index: 2 });
+        assert_eq!(error.to_string(), "can't sort floats with a NaN at index 2");
+
+        // Nothing moved
+        assert_eq!(&items[..2], [3.0, 1.0]);
+
+        let mut items = [3.0, 1.0, 2.0];
+        sort_floats(&mut items, NanPolicy::Error).unwrap();
+        assert_eq!(items, [1.0, 2.0, 3.0]);
+    }
+}
@@ -0,0 +1,240 @@
+use core::cmp::Ordering;
+use core::iter::FusedIterator;
+
+/// The comparison that `kmerge` uses, which has to be nameable.
+type Compare<T> = fn(&T, &T) -> Ordering;
+
+/// Merges sorted iterators into one sorted iterator.
+///
+/// See `kmerge_by`.
+pub fn kmerge<T, I>(iterables: I) -> KMerge<<I::Item as IntoIterator>::IntoIter, Compare<T>>
+where
+    T: Ord,
+    I: IntoIterator,
+    I::Item: IntoIterator<Item = T>,
+{
+    kmerge_by(iterables, T::cmp as Compare<T>)
+}
+
+/// Merges iterators that are each sorted by `compare` into one sorted iterator.
+///
+/// This is stable: items that compare equal come out in the order of the
+/// iterators they came from, and then in the order each iterator gave them.
+///
+/// It uses a loser tree, which picks the next item out of `k` iterators with
+/// only `lg k` comparisons, so merging `N` items takes `O(N lg k)` time.
+/// Merging pairs of iterators over and over takes the same number of
+/// comparisons, but passes each item through `lg k` merges to get there.
+///
+/// ```
+/// use algos::kmerge;
+///
+/// let shards = vec![vec![1, 4, 7], vec![2, 5], vec![], vec![3, 6, 9]];
+/// let merged: Vec<_> = kmerge(shards).collect();
+/// assert_eq!(merged, [1, 2, 3, 4, 5, 6, 7, 9]);
+/// ```
+pub fn kmerge_by<T, I, F>(
+    iterables: I,
+    compare: F,
+) -> KMerge<<I::Item as IntoIterator>::IntoIter, F>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator<Item = T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut sources: Vec<_> = iterables.into_iter().map(IntoIterator::into_iter).collect();
+    let heads = sources.iter_mut().map(Iterator::next).collect();
+
+    let mut merge = KMerge {
+        tree: vec![0; sources.len().max(1)],
+        sources,
+        heads,
+        compare,
+    };
+    if !merge.sources.is_empty() {
+        merge.tree[0] = merge.build(1);
+    }
+
+    merge
+}
+
+/// An iterator that merges sorted iterators. See `kmerge_by`.
+///
+/// The loser tree is a complete binary tree, stored like a binary heap, with
+/// the `k` iterators as its leaves: leaf `i` is node `k + i`. Each internal
+/// node holds the iterator that lost the match played there, and node 0 holds
+/// the overall winner. After taking the winner's item, its next item only has
+/// to replay the matches on the way from its leaf to the root.
+pub struct KMerge<I: Iterator, F> {
+    sources: Vec<I>,
+
+    /// The next item from each iterator, or `None` once it runs out
+    heads: Vec<Option<I::Item>>,
+
+    /// Which iterator lost at each node, with the winner at 0
+    tree: Vec<usize>,
+
+    compare: F,
+}
+
+impl<I, F> KMerge<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    /// Whether iterator `a`'s next item goes before iterator `b`'s.
+    ///
+    /// Iterators that have run out lose to everything, and ties go to the
+    /// earlier iterator, which is what makes the merge stable.
+    fn beats(&mut self, a: usize, b: usize) -> bool {
+        match (&self.heads[a], &self.heads[b]) {
+            (Some(x), Some(y)) => (self.compare)(x, y).then(a.cmp(&b)) == Ordering::Less,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => a < b,
+        }
+    }
+
+    /// Plays every match under `node`, storing the losers, and returns the winner.
+    fn build(&mut self, node: usize) -> usize {
+        let k = self.sources.len();
+        if node >= k {
+            return node - k;
+        }
+
+        let left = self.build(2 * node);
+        let right = self.build(2 * node + 1);
+        let (winner, loser) = if self.beats(right, left) {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        self.tree[node] = loser;
+        winner
+    }
+
+    /// Replays the matches from iterator `source`'s leaf up to the root.
+    fn replay(&mut self, source: usize) {
+        let mut winner = source;
+        let mut node = (self.sources.len() + source) / 2;
+        while node > 0 {
+            if self.beats(self.tree[node], winner) {
+                core::mem::swap(&mut self.tree[node], &mut winner);
+            }
+            node /= 2;
+        }
+        self.tree[0] = winner;
+    }
+}
+
+impl<I, F> Iterator for KMerge<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let winner = self.tree[0];
+        let item = self.heads.get_mut(winner)?.take()?;
+
+        // Iterators that have run out never win again, so they're never called
+        // after returning `None`.
+        self.heads[winner] = self.sources[winner].next();
+        self.replay(winner);
+
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let waiting = self.heads.iter().filter(|head| head.is_some()).count();
+        self.heads
+            .iter()
+            .zip(self.sources.iter())
+            .filter(|(head, _)| head.is_some())
+            .map(|(_, source)| source.size_hint())
+            .fold(
+                (waiting, Some(waiting)),
+                |(low, high), (source_low, source_high)| {
+                    (
+                        low.saturating_add(source_low),
+                        high.and_then(|high| high.checked_add(source_high?)),
+                    )
+                },
+            )
+    }
+}
+
+impl<I, F> FusedIterator for KMerge<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    #[test]
+    fn check_kmerge_random() {
+        let mut rng = thread_rng();
+        for k in 0..20 {
+            let mut sources: Vec<Vec<i32>> = (0..k)
+                .map(|_| {
+                    let len = rng.gen_range(0, 50);
+                    (0..len).map(|_| rng.gen_range(0, 100)).collect()
+                })
+                .collect();
+            for source in sources.iter_mut() {
+                source.sort_unstable();
+            }
+
+            let mut expected: Vec<i32> = sources.concat();
+            expected.sort_unstable();
+
+            let merge = kmerge(sources.clone());
+            assert_eq!(merge.size_hint(), (expected.len(), Some(expected.len())));
+            assert_eq!(merge.collect::<Vec<_>>(), expected, "k = {}", k);
+        }
+    }
+
+    #[test]
+    fn check_kmerge_is_stable() {
+        // Tag each item with its iterator and position, and merge by value only
+        let sources: Vec<Vec<(i32, usize, usize)>> = (0..7)
+            .map(|source| {
+                (0..30)
+                    .map(|i| ((i / 4 + source % 3) as i32, source, i))
+                    .collect()
+            })
+            .collect();
+
+        let merged: Vec<_> = kmerge_by(sources.clone(), |a, b| a.0.cmp(&b.0)).collect();
+
+        let mut expected = sources.concat();
+        expected.sort_by_key(|item| item.0);
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn check_kmerge_by_descending() {
+        let sources = vec![vec![5, 3, 1], vec![6, 4, 2], vec![]];
+        let merged: Vec<_> = kmerge_by(sources, |a: &i32, b| b.cmp(a)).collect();
+        assert_eq!(merged, [6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn check_kmerge_empty() {
+        let sources: Vec<Vec<i32>> = vec![];
+        assert_eq!(kmerge(sources).next(), None);
+
+        let mut merge = kmerge(vec![Vec::<i32>::new(), vec![]]);
+        assert_eq!(merge.next(), None);
+        assert_eq!(merge.next(), None);
+    }
+}
@@ -0,0 +1,456 @@
+use core::cmp::Ordering;
+use core::mem::MaybeUninit;
+
+use crate::stats::{self, Scratch};
+use crate::trace::Recursion;
+
+/// How much scratch memory `merge_sort` may use, and so how fast it is.
+///
+/// | Strategy     | Extra space  | Time            |
+/// |--------------|--------------|-----------------|
+/// | `Buffered`   | `O(N)`       | `O(N lg N)`     |
+/// | `SqrtBuffer` | `O(sqrt N)`  | `O(N lg^2 N)`*  |
+/// | `InPlace`    | `O(1)`       | `O(N lg^2 N)`   |
+///
+/// \* Merges that fit into the buffer run in linear time, so in practice this
+/// lands between the other two.
+///
+/// All of them are stable, and none of them need to clone items.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Merge with a buffer half the size of the items.
+    #[default]
+    Buffered,
+
+    /// Merge with a buffer of `sqrt(N)` items.
+    ///
+    /// Merges whose shorter run fits in the buffer use it. Longer merges are
+    /// split into smaller ones by rotating items, until they fit.
+    SqrtBuffer,
+
+    /// Merge in place by rotating items, without any buffer.
+    ///
+    /// This still needs `O(lg N)` stack space for recursion.
+    InPlace,
+}
+
+/// Options for `merge_sort`.
+///
+/// ```
+/// use algos::{MergeSort, MergeStrategy};
+///
+/// // Items don't need to be `Clone`
+/// let mut items: Vec<Box<i32>> = vec![Box::new(3), Box::new(1), Box::new(2)];
+/// MergeSort {
+///     strategy: MergeStrategy::InPlace,
+/// }
+/// .sort(&mut items);
+/// assert_eq!(items, [Box::new(1), Box::new(2), Box::new(3)]);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeSort {
+    pub strategy: MergeStrategy,
+}
+
+impl MergeSort {
+    pub fn sort<T: Ord>(&self, items: &mut [T]) {
+        self.sort_by(items, T::cmp);
+    }
+
+    pub fn sort_by<T, F>(&self, items: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        // Zero sized items all look the same, so there's nothing to sort.
+        // (And our pointer math can't handle them.)
+        if core::mem::size_of::<T>() == 0 {
+            return;
+        }
+
+        let capacity = match self.strategy {
+            MergeStrategy::Buffered => items.len() / 2,
+            MergeStrategy::SqrtBuffer => items.len().isqrt(),
+            MergeStrategy::InPlace => 0,
+        };
+
+        // Re-use the scratch buffer across each recurse. Each merge finishes
+        // with it before the next one starts. `par_merge_sort` splits it
+        // between tasks instead, since they merge at the same time.
+        let mut scratch: Vec<T> = Vec::with_capacity(capacity);
+        let _scratch = Scratch::new::<T>(scratch.capacity());
+        let mut is_less = |a: &T, b: &T| compare(a, b) == Ordering::Less;
+
+        merge_helper(scratch.spare_capacity_mut(), items, &mut is_less, &mut ());
+    }
+
+    pub fn sort_by_key<T, K, F>(&self, items: &mut [T], mut f: F)
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.sort_by(items, |a, b| f(a).cmp(&f(b)));
+    }
+}
+
+/// Something that merges report inversions to, as they find them.
+///
+/// An inversion is a pair of items that are out of order. Each merge finds the
+/// ones between its two runs as it goes, at no extra cost, which is how
+/// `presortedness::count_inversions` counts them all. Sorts pass `()`, which
+/// ignores them.
+pub(crate) trait Inversions {
+    fn found(&mut self, count: usize);
+}
+
+impl Inversions for () {
+    #[inline(always)]
+    fn found(&mut self, _count: usize) {}
+}
+
+impl Inversions for u64 {
+    #[inline(always)]
+    fn found(&mut self, count: usize) {
+        *self += count as u64;
+    }
+}
+
+pub(crate) fn merge_helper<T, F, I>(
+    scratch: &mut [MaybeUninit<T>],
+    items: &mut [T],
+    is_less: &mut F,
+    inversions: &mut I,
+) where
+    F: FnMut(&T, &T) -> bool,
+    I: Inversions,
+{
+    // If our slice is trivially sorted, we can stop recursing.
+    if items.len() <= 1 {
+        return;
+    }
+    let _recursion = Recursion::enter(items);
+
+    // 1. Pick a pivot point and split the items into two sub arrays
+    let pivot = items.len() / 2;
+    let (left, right) = items.split_at_mut(pivot);
+
+    // 2. Recurse to sort the sub arrays as smaller problems
+    merge_helper(scratch, left, is_less, inversions);
+    merge_helper(scratch, right, is_less, inversions);
+
+    // 3. Merge the two sorted sub-arrays, using as much scratch memory as we have
+    merge(scratch, items, pivot, is_less, inversions);
+}
+
+/// Merges the sorted runs `items[..mid]` and `items[mid..]`.
+///
+/// Merges whose shorter run fits in `scratch` move that run into it, and merge
+/// in linear time. Anything longer is split into two smaller merges by
+/// rotating items, which moves every item at most once per level.
+///
+/// Every inversion between the two runs is reported to `inversions`.
+pub(crate) fn merge<T, F, I>(
+    scratch: &mut [MaybeUninit<T>],
+    items: &mut [T],
+    mid: usize,
+    is_less: &mut F,
+    inversions: &mut I,
+) where
+    F: FnMut(&T, &T) -> bool,
+    I: Inversions,
+{
+    let len = items.len();
+    if mid == 0 || mid == len {
+        return;
+    }
+
+    // Already in order, which sorted input hits every time.
+    if !is_less(&items[mid], &items[mid - 1]) {
+        return;
+    }
+
+    // Two items that are out of order just need to trade places.
+    // Splitting them below wouldn't make any progress.
+    if len == 2 {
+        stats::swap(items, 0, 1);
+        inversions.found(1);
+        return;
+    }
+
+    if mid.min(len - mid) <= scratch.len() {
+        // SAFETY: the shorter run fits in `scratch`
+        unsafe {
+            if mid <= len - mid {
+                merge_lo(
+                    items,
+                    mid,
+                    scratch.as_mut_ptr() as *mut T,
+                    is_less,
+                    inversions,
+                );
+            } else {
+                merge_hi(
+                    items,
+                    mid,
+                    scratch.as_mut_ptr() as *mut T,
+                    is_less,
+                    inversions,
+                );
+            }
+        }
+        return;
+    }
+
+    // Split the longer run in half. Then find where its middle item goes in the
+    // other run, and split that run there too.
+    //
+    //     [ A1 | A2 ][ B1 | B2 ]
+    //
+    // Everything in A1 and B1 goes before everything in A2 and B2, so rotating
+    // A2 past B1 gives us two smaller merges:
+    //
+    //     [ A1 | B1 ][ A2 | B2 ]
+    //
+    // Stability means that ties between A and B keep A first. Every item in A2
+    // is greater than every item in B1, so that's A2 * B1 inversions, which
+    // the rotation fixes.
+    let (left, right) = items.split_at(mid);
+    let (left_cut, right_cut) = if left.len() > right.len() {
+        let left_cut = left.len() / 2;
+        let key = &left[left_cut];
+        (left_cut, right.partition_point(|x| is_less(x, key)))
+    } else {
+        let right_cut = right.len() / 2;
+        let key = &right[right_cut];
+        (left.partition_point(|x| !is_less(key, x)), right_cut)
+    };
+
+    items[left_cut..mid + right_cut].rotate_left(mid - left_cut);
+    stats::wrote(&items[left_cut..mid + right_cut]);
+    inversions.found((mid - left_cut) * right_cut);
+    let new_mid = left_cut + right_cut;
+
+    let (first, second) = items.split_at_mut(new_mid);
+    merge(scratch, first, left_cut, is_less, inversions);
+    merge(scratch, second, mid - left_cut, is_less, inversions);
+}
+
+/// Merges `items[..mid]` and `items[mid..]`, working from the front.
+///
+/// # Safety
+///
+/// `buf` must have room for `mid` items, and must not overlap `items`.
+unsafe fn merge_lo<T, F, I>(
+    items: &mut [T],
+    mid: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    inversions: &mut I,
+) where
+    F: FnMut(&T, &T) -> bool,
+    I: Inversions,
+{
+    let v = items.as_mut_ptr();
+    let right_end = v.add(items.len());
+
+    // We move the left run into `buf`, which leaves a hole in `items` where it was.
+    // Items are moved from `buf` or the right run into the front of the hole,
+    // which slides the hole to the right until it's gone.
+    //
+    // `hole` tracks which items are still in `buf`, and where they go.
+    // If `is_less` panics, `hole` is dropped and copies them all back, so
+    // `items` never has duplicated or missing items.
+    stats::copy_nonoverlapping(v, buf, mid);
+    let mut hole = MergeHole {
+        start: buf,
+        end: buf.add(mid),
+        dest: v,
+    };
+    let mut right = v.add(mid);
+
+    while hole.start < hole.end && right < right_end {
+        // Ties take from the left, to stay stable
+        if is_less(&*right, &*hole.start) {
+            // It jumps ahead of everything still in the left run
+            inversions.found(hole.end.offset_from(hole.start) as usize);
+            stats::copy_nonoverlapping(right, hole.dest, 1);
+            right = right.add(1);
+        } else {
+            stats::copy_nonoverlapping(hole.start, hole.dest, 1);
+            hole.start = hole.start.add(1);
+        }
+        hole.dest = hole.dest.add(1);
+    }
+
+    // Dropping `hole` moves anything left in `buf` into place.
+    // Anything left in the right run is already in place.
+}
+
+/// Merges `items[..mid]` and `items[mid..]`, working from the back.
+///
+/// # Safety
+///
+/// `buf` must have room for `items.len() - mid` items, and must not overlap `items`.
+unsafe fn merge_hi<T, F, I>(
+    items: &mut [T],
+    mid: usize,
+    buf: *mut T,
+    is_less: &mut F,
+    inversions: &mut I,
+) where
+    F: FnMut(&T, &T) -> bool,
+    I: Inversions,
+{
+    let len = items.len();
+    let v = items.as_mut_ptr();
+
+    // This mirrors `merge_lo`: we move the right run into `buf`, and fill the
+    // hole that it leaves from the back. The largest remaining item goes last.
+    //
+    // `hole.start..hole.end` are the items still in `buf`. They belong right
+    // after whatever is left of the left run, at `hole.dest`.
+    stats::copy_nonoverlapping(v.add(mid), buf, len - mid);
+    let mut hole = MergeHole {
+        start: buf,
+        end: buf.add(len - mid),
+        dest: v.add(mid),
+    };
+    let mut out = v.add(len);
+
+    while v < hole.dest && hole.start < hole.end {
+        let left_last = hole.dest.sub(1);
+        let right_last = hole.end.sub(1);
+        out = out.sub(1);
+
+        // Ties take from the right, since we're filling from the back
+        if is_less(&*right_last, &*left_last) {
+            // It goes after everything still in the right run
+            inversions.found(hole.end.offset_from(hole.start) as usize);
+            stats::copy(left_last, out, 1);
+            hole.dest = left_last;
+        } else {
+            stats::copy_nonoverlapping(right_last, out, 1);
+            hole.end = right_last;
+        }
+    }
+
+    // Dropping `hole` moves anything left in `buf` into place.
+    // Anything left in the left run is already in place.
+}
+
+/// Items that a merge moved out into a buffer, and where they belong.
+///
+/// When this is dropped, it moves `start..end` to `dest`. That finishes a merge
+/// normally, and restores every item when a comparison panics.
+pub(super) struct MergeHole<T> {
+    pub(super) start: *mut T,
+    pub(super) end: *mut T,
+    pub(super) dest: *mut T,
+}
+
+impl<T> Drop for MergeHole<T> {
+    fn drop(&mut self) {
+        // SAFETY: `start..end` are valid items in a buffer, and `dest` is the
+        // start of a hole of the same length in the items being sorted.
+        unsafe {
+            let len = self.end.offset_from(self.start) as usize;
+            stats::copy_nonoverlapping(self.start, self.dest, len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    const STRATEGIES: &[MergeStrategy] = &[
+        MergeStrategy::Buffered,
+        MergeStrategy::SqrtBuffer,
+        MergeStrategy::InPlace,
+    ];
+
+    #[test]
+    fn check_merge_strategies_are_stable() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for strategy in STRATEGIES.iter().copied() {
+            for len in [0, 1, 2, 3, 10, 100, 1_000, 4_567].iter().copied() {
+                let mut items: Vec<(u8, String)> = (0..len)
+                    .map(|i| (rng.gen_range(0, 20), format!("{:04}", i)))
+                    .collect();
+                let mut expected = items.clone();
+                expected.sort();
+
+                // Sorting by just the first field must keep the second field in order.
+                MergeSort { strategy }.sort_by_key(&mut items, |(k, _s)| *k);
+                assert_eq!(items, expected, "{:?}", strategy);
+            }
+        }
+    }
+
+    #[test]
+    fn check_sorts_without_clone() {
+        trait Named {
+            fn name(&self) -> &str;
+        }
+
+        struct File(&'static str);
+        impl Named for File {
+            fn name(&self) -> &str {
+                self.0
+            }
+        }
+
+        for strategy in STRATEGIES.iter().copied() {
+            let mut items: Vec<Box<dyn Named>> = ["c", "a", "d", "b"]
+                .iter()
+                .map(|name| Box::new(File(name)) as Box<dyn Named>)
+                .collect();
+
+            MergeSort { strategy }.sort_by(&mut items, |a, b| a.name().cmp(b.name()));
+            let names: Vec<_> = items.iter().map(|f| f.name()).collect();
+            assert_eq!(names, ["a", "b", "c", "d"]);
+        }
+    }
+
+    #[test]
+    fn check_sorted_is_linear() {
+        // Each merge only needs a single compare to notice it's already in order.
+        let mut items: Vec<i32> = (0..1_024).collect();
+        let mut compares = 0;
+        MergeSort::default().sort_by(&mut items, |a, b| {
+            compares += 1;
+            a.cmp(b)
+        });
+
+        assert_eq!(compares, items.len() - 1);
+    }
+
+    #[test]
+    fn check_panic_keeps_items() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        for strategy in STRATEGIES.iter().copied() {
+            let mut items: Vec<Box<i32>> =
+                (0..1_000).map(|i| Box::new((i * 7919) % 1_000)).collect();
+            let mut compares = 0;
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                MergeSort { strategy }.sort_by(&mut items, |a, b| {
+                    compares += 1;
+                    if compares == 5_000 {
+                        panic!("Oops");
+                    }
+                    a.cmp(b)
+                })
+            }));
+            assert!(result.is_err());
+
+            // Every item should still be there, exactly once.
+            let mut items: Vec<i32> = items.into_iter().map(|b| *b).collect();
+            items.sort();
+            assert_eq!(items, (0..1_000).collect::<Vec<_>>());
+        }
+    }
+}
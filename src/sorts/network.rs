@@ -0,0 +1,533 @@
+use core::cmp::Ordering;
+use core::ptr;
+
+use crate::{stats, trace};
+
+/// The most items that a [`SortingNetwork`] can sort.
+pub const MAX_NETWORK_SIZE: usize = 32;
+
+/// Enough comparators for every kind of network, up to `MAX_NETWORK_SIZE` items.
+const MAX_COMPARATORS: usize = 256;
+
+/// Ways to build a sorting network for any number of items.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetworkKind {
+    /// Bose and Nelson's 1962 recursive merging network. This is optimal for up
+    /// to 8 items, but uses more layers than the others.
+    BoseNelson,
+
+    /// Batcher's odd-even merge sort, which is the smallest of these for most
+    /// sizes over 12 items. Networks for sizes between powers of 2 are cut down from the
+    /// next power of 2.
+    OddEvenMerge,
+
+    /// Batcher's bitonic sort. This uses more comparators than odd-even merge
+    /// sort, but every layer of it has the same simple shape.
+    Bitonic,
+}
+
+/// A fixed sequence of compare and swaps, which sorts any `inputs` items.
+///
+/// Which items get compared never depends on the items, and the swaps are done
+/// with conditional moves, so there are no branches for the CPU to mispredict.
+/// That makes networks faster than `insertion_sort` for random items, once there
+/// are more than about 8 of them.
+///
+/// Networks are built by `const fn`s, so they can be built at compile time.
+/// `network_sort` does that, picking the best network for each size.
+///
+/// ```
+/// use algos::{NetworkKind, SortingNetwork};
+///
+/// const NETWORK: SortingNetwork = SortingNetwork::new(NetworkKind::BoseNelson, 4);
+/// assert_eq!(NETWORK.comparators(), [(0, 1), (2, 3), (0, 2), (1, 3), (1, 2)]);
+/// assert!(NETWORK.verify());
+///
+/// let mut items = ['d', 'a', 'c', 'b'];
+/// NETWORK.sort(&mut items);
+/// assert_eq!(items, ['a', 'b', 'c', 'd']);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SortingNetwork {
+    inputs: usize,
+    comparators: [(u8, u8); MAX_COMPARATORS],
+    len: usize,
+}
+
+impl SortingNetwork {
+    /// Builds a network of `kind` that sorts `inputs` items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `inputs > MAX_NETWORK_SIZE`. In a `const`, that's a compile error.
+    pub const fn new(kind: NetworkKind, inputs: usize) -> Self {
+        assert!(
+            inputs <= MAX_NETWORK_SIZE,
+            "sorting networks only go up to 32 items"
+        );
+
+        let mut network = SortingNetwork {
+            inputs,
+            comparators: [(0, 0); MAX_COMPARATORS],
+            len: 0,
+        };
+        match kind {
+            NetworkKind::BoseNelson => network.bose_nelson_sort(0, inputs),
+            NetworkKind::OddEvenMerge => network.odd_even_merge_sort(),
+            NetworkKind::Bitonic => network.bitonic_sort(),
+        }
+
+        network
+    }
+
+    /// Builds the network with the fewest comparators for `inputs` items, out
+    /// of every [`NetworkKind`]. Ties go to the network with fewer layers.
+    ///
+    /// These are optimal for up to 8 items, and within about 15% of the best
+    /// known networks up to 32.
+    pub const fn best(inputs: usize) -> Self {
+        let kinds = [
+            NetworkKind::BoseNelson,
+            NetworkKind::OddEvenMerge,
+            NetworkKind::Bitonic,
+        ];
+
+        let mut best = SortingNetwork::new(kinds[0], inputs);
+        let mut i = 1;
+        while i < kinds.len() {
+            let network = SortingNetwork::new(kinds[i], inputs);
+            if network.len < best.len || (network.len == best.len && network.depth() < best.depth())
+            {
+                best = network;
+            }
+            i += 1;
+        }
+
+        best
+    }
+
+    /// How many items this sorts.
+    pub const fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// Each compare and swap, in order: `(a, b)` swaps `items[a]` and
+    /// `items[b]` if `items[b] < items[a]`, where `a < b`.
+    pub fn comparators(&self) -> &[(u8, u8)] {
+        &self.comparators[..self.len]
+    }
+
+    /// How many layers of comparators there are, where every comparator in a
+    /// layer touches different items. Those could all be done at once.
+    pub const fn depth(&self) -> usize {
+        let mut depths = [0; MAX_NETWORK_SIZE];
+        let mut depth = 0;
+
+        let mut i = 0;
+        while i < self.len {
+            let (a, b) = self.comparators[i];
+            let (a, b) = (a as usize, b as usize);
+
+            let layer = if depths[a] > depths[b] {
+                depths[a]
+            } else {
+                depths[b]
+            } + 1;
+            depths[a] = layer;
+            depths[b] = layer;
+            if layer > depth {
+                depth = layer;
+            }
+            i += 1;
+        }
+
+        depth
+    }
+
+    /// Checks that this really sorts every input. See `is_sorting_network`.
+    pub fn verify(&self) -> bool {
+        is_sorting_network(self.inputs, self.comparators())
+    }
+
+    pub fn sort<T: Ord>(&self, items: &mut [T]) {
+        self.sort_by(items, T::cmp);
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `items.len()` isn't `self.inputs()`.
+    #[inline]
+    pub fn sort_by<T, F>(&self, items: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        assert_eq!(
+            items.len(),
+            self.inputs,
+            "SortingNetwork::sort() needs exactly as many items as inputs"
+        );
+
+        // `compare_exchange` doesn't know which items it swapped, so traces take
+        // the slow way.
+        if trace::tracing() {
+            for (a, b) in self.comparators().iter().copied() {
+                let (a, b) = (a as usize, b as usize);
+                if compare(&items[b], &items[a]) == Ordering::Less {
+                    stats::swap(items, a, b);
+                }
+            }
+            return;
+        }
+
+        for (a, b) in self.comparators().iter().copied() {
+            compare_exchange(items, a as usize, b as usize, &mut compare);
+        }
+    }
+
+    const fn push(&mut self, a: usize, b: usize) {
+        assert!(self.len < MAX_COMPARATORS);
+        self.comparators[self.len] = (a as u8, b as u8);
+        self.len += 1;
+    }
+
+    /// Sorts `items[start..start + len]` by sorting each half, and merging them.
+    const fn bose_nelson_sort(&mut self, start: usize, len: usize) {
+        if len > 1 {
+            let half = len / 2;
+            self.bose_nelson_sort(start, half);
+            self.bose_nelson_sort(start + half, len - half);
+            self.bose_nelson_merge(start, half, start + half, len - half);
+        }
+    }
+
+    /// Merges the sorted `x` items from `i`, and the sorted `y` items from `j`.
+    const fn bose_nelson_merge(&mut self, i: usize, x: usize, j: usize, y: usize) {
+        match (x, y) {
+            (0, _) | (_, 0) => {}
+            (1, 1) => self.push(i, j),
+            (1, 2) => {
+                self.push(i, j + 1);
+                self.push(i, j);
+            }
+            (2, 1) => {
+                self.push(i, j);
+                self.push(i + 1, j);
+            }
+            _ => {
+                let a = x / 2;
+                let b = if x % 2 == 1 { y / 2 } else { y.div_ceil(2) };
+                self.bose_nelson_merge(i, a, j, b);
+                self.bose_nelson_merge(i + a, x - a, j + b, y - b);
+                self.bose_nelson_merge(i + a, x - a, j, b);
+            }
+        }
+    }
+
+    /// Batcher's odd-even merge sort, for the next power of 2, without the
+    /// comparators past the end.
+    ///
+    /// Those comparators would only ever see items larger than every real item,
+    /// which never move, so dropping them still leaves a sorting network.
+    const fn odd_even_merge_sort(&mut self) {
+        let n = self.inputs;
+
+        // Sorted blocks of `p` items are merged into blocks of `2p`, comparing
+        // items `k` apart for shrinking `k`.
+        let mut p = 1;
+        while p < n {
+            let mut k = p;
+            while k >= 1 {
+                let mut j = k % p;
+                while j + k < n {
+                    let mut i = 0;
+                    while i < k && i + j + k < n {
+                        // Only compare items within the same block of `2p`
+                        if (i + j) / (2 * p) == (i + j + k) / (2 * p) {
+                            self.push(i + j, i + j + k);
+                        }
+                        i += 1;
+                    }
+                    j += 2 * k;
+                }
+                k /= 2;
+            }
+            p *= 2;
+        }
+    }
+
+    /// Batcher's bitonic sort, for the next power of 2, without the comparators
+    /// past the end, like `odd_even_merge_sort`.
+    ///
+    /// The usual bitonic sort sorts every other block backwards. Instead, this
+    /// compares the first half of each block with the second half reversed,
+    /// so that every comparator puts the smaller item first.
+    const fn bitonic_sort(&mut self) {
+        let n = self.inputs;
+
+        let mut p = 1;
+        while p < n {
+            // Merge each pair of sorted blocks of `p`, starting by comparing
+            // the first half with the second half reversed.
+            let mut start = 0;
+            while start < n {
+                let mut i = 0;
+                while i < p {
+                    let b = start + 2 * p - 1 - i;
+                    if b < n {
+                        self.push(start + i, b);
+                    }
+                    i += 1;
+                }
+                start += 2 * p;
+            }
+
+            // Then it's a plain bitonic merge in each half
+            let mut k = p / 2;
+            while k >= 1 {
+                let mut start = 0;
+                while start < n {
+                    let mut i = start;
+                    while i < start + k && i + k < n {
+                        self.push(i, i + k);
+                        i += 1;
+                    }
+                    start += 2 * k;
+                }
+                k /= 2;
+            }
+            p *= 2;
+        }
+    }
+}
+
+/// Puts the smaller of `items[a]` and `items[b]` in `items[a]`, without branching.
+///
+/// `a < b < items.len()` must hold, which isn't checked in release builds.
+///
+/// A branch on the comparison would be mispredicted about half the time, which
+/// costs more than the comparison. Instead, this always writes both items, from
+/// a pair of pointers that are picked with a conditional move.
+#[inline]
+fn compare_exchange<T, F>(items: &mut [T], a: usize, b: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    debug_assert!(a < b && b < items.len());
+
+    let swap = compare(&items[b], &items[a]) == Ordering::Less;
+    stats::swaps(swap as usize);
+
+    // SAFETY: Every network that we build has `a < b < inputs`, and `sort_by`
+    // checks that there are `inputs` items, so `a` and `b` are different, and
+    // in bounds. Both items are read
+    // before either is written, and nothing in between can panic, so each
+    // item ends up in exactly one place.
+    unsafe {
+        let (pa, pb) = (items.as_mut_ptr().add(a), items.as_mut_ptr().add(b));
+        let (first, second) = if swap { (pb, pa) } else { (pa, pb) };
+        let (first, second) = (ptr::read(first), ptr::read(second));
+        ptr::write(pa, first);
+        ptr::write(pb, second);
+    }
+}
+
+/// Checks that `comparators` sort every possible list of `inputs` items.
+///
+/// By the 0-1 principle, a network sorts everything if it sorts every list of
+/// 0s and 1s, so this checks all `2^inputs` of those. It checks 64 at once,
+/// with a bit for each in a `u64` for each item, where a comparator is an AND
+/// and an OR. That's quick up to about 24 items, but takes minutes for 32.
+///
+/// Each comparator `(a, b)` must have `a < b < inputs`.
+pub fn is_sorting_network(inputs: usize, comparators: &[(u8, u8)]) -> bool {
+    assert!(
+        inputs <= MAX_NETWORK_SIZE,
+        "is_sorting_network() only goes up to 32 items"
+    );
+    for (a, b) in comparators.iter().copied() {
+        assert!(
+            a < b && (b as usize) < inputs,
+            "is_sorting_network() found a comparator ({}, {}) that isn't a < b < {}",
+            a,
+            b,
+            inputs
+        );
+    }
+
+    // The first 6 items take every combination across the bits of a `u64`.
+    // The rest are all 0s or all 1s, depending on the bits of `batch`.
+    const PATTERNS: [u64; 6] = [
+        0xAAAA_AAAA_AAAA_AAAA,
+        0xCCCC_CCCC_CCCC_CCCC,
+        0xF0F0_F0F0_F0F0_F0F0,
+        0xFF00_FF00_FF00_FF00,
+        0xFFFF_0000_FFFF_0000,
+        0xFFFF_FFFF_0000_0000,
+    ];
+    let batches = 1_u64 << inputs.saturating_sub(PATTERNS.len());
+
+    let mut items = [0_u64; MAX_NETWORK_SIZE];
+    for batch in 0..batches {
+        for (i, item) in items.iter_mut().enumerate().take(inputs) {
+            *item = match PATTERNS.get(i) {
+                Some(pattern) => *pattern,
+                None if batch >> (i - PATTERNS.len()) & 1 == 1 => !0,
+                None => 0,
+            };
+        }
+
+        for (a, b) in comparators.iter().copied() {
+            let (x, y) = (items[a as usize], items[b as usize]);
+            items[a as usize] = x & y;
+            items[b as usize] = x | y;
+        }
+
+        // Sorted 0s and 1s never have a 1 before a 0
+        if (1..inputs).any(|i| items[i - 1] & !items[i] != 0) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// The best network for each size, built at compile time.
+struct BestNetwork<const N: usize>;
+
+impl<const N: usize> BestNetwork<N> {
+    // A reference, so that it's a static, and not copied onto the stack for every sort.
+    const NETWORK: &'static SortingNetwork = &SortingNetwork::best(N);
+}
+
+#[inline]
+pub fn network_sort<T: Ord, const N: usize>(items: &mut [T; N]) {
+    network_sort_by(items, T::cmp);
+}
+
+/// Sorts a small array with the best [`SortingNetwork`] for its size.
+///
+/// The network is built at compile time, so sizes over `MAX_NETWORK_SIZE`
+/// don't compile.
+///
+/// ```
+/// use algos::network_sort;
+///
+/// let mut items = [5, 2, 8, 1, 9, 3];
+/// network_sort(&mut items);
+/// assert_eq!(items, [1, 2, 3, 5, 8, 9]);
+/// ```
+#[inline]
+pub fn network_sort_by<T, F, const N: usize>(items: &mut [T; N], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    BestNetwork::<N>::NETWORK.sort_by(items, compare);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    const KINDS: [NetworkKind; 3] = [
+        NetworkKind::BoseNelson,
+        NetworkKind::OddEvenMerge,
+        NetworkKind::Bitonic,
+    ];
+
+    #[test]
+    fn check_networks_sort() {
+        check_networks_sort_up_to(0..=24);
+    }
+
+    /// These take minutes, even in release builds, so they're left for
+    /// `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn check_big_networks_sort() {
+        check_networks_sort_up_to(25..=MAX_NETWORK_SIZE);
+    }
+
+    /// Checks every kind of network for each size in `sizes`, which covers
+    /// `best` too, since it picks one of them.
+    fn check_networks_sort_up_to(sizes: core::ops::RangeInclusive<usize>) {
+        for inputs in sizes {
+            for kind in KINDS.iter().copied() {
+                let network = SortingNetwork::new(kind, inputs);
+                assert!(network.verify(), "{:?} for {} items", kind, inputs);
+            }
+        }
+    }
+
+    #[test]
+    fn check_best_network_sizes() {
+        // The optimal number of comparators, which `best` only finds for up to 8 items
+        let optimal = [0, 0, 1, 3, 5, 9, 12, 16, 19];
+        for (inputs, optimal) in optimal.iter().copied().enumerate() {
+            assert_eq!(SortingNetwork::best(inputs).comparators().len(), optimal);
+        }
+
+        assert_eq!(
+            SortingNetwork::new(NetworkKind::OddEvenMerge, 16)
+                .comparators()
+                .len(),
+            63
+        );
+        assert_eq!(
+            SortingNetwork::new(NetworkKind::OddEvenMerge, 16).depth(),
+            10
+        );
+        assert_eq!(
+            SortingNetwork::new(NetworkKind::Bitonic, 16)
+                .comparators()
+                .len(),
+            80
+        );
+        assert_eq!(SortingNetwork::new(NetworkKind::Bitonic, 16).depth(), 10);
+    }
+
+    #[test]
+    fn check_broken_network() {
+        let network = SortingNetwork::best(10);
+        let comparators = network.comparators();
+        for skip in 0..comparators.len() {
+            let mut broken = comparators.to_vec();
+            broken.remove(skip);
+            assert!(
+                !is_sorting_network(10, &broken),
+                "without comparator {}",
+                skip
+            );
+        }
+    }
+
+    #[test]
+    fn check_network_sort() {
+        fn check<const N: usize>(rng: &mut impl Rng) {
+            let mut items = [0; N];
+            for _ in 0..100 {
+                items.iter_mut().for_each(|x| *x = rng.gen_range(0, 10));
+                let mut expected = items;
+                expected.sort_unstable();
+                network_sort(&mut items);
+                assert_eq!(items, expected);
+            }
+        }
+
+        let mut rng = thread_rng();
+        check::<0>(&mut rng);
+        check::<1>(&mut rng);
+        check::<2>(&mut rng);
+        check::<7>(&mut rng);
+        check::<16>(&mut rng);
+        check::<23>(&mut rng);
+        check::<32>(&mut rng);
+
+        let mut items = ["b", "c", "a"];
+        network_sort_by(&mut items, |a, b| b.cmp(a));
+        assert_eq!(items, ["c", "b", "a"]);
+    }
+}
@@ -0,0 +1,432 @@
+use core::cmp::Ordering;
+use core::mem::MaybeUninit;
+use core::num::NonZeroUsize;
+use std::panic;
+use std::thread;
+
+use super::merge::{merge, merge_helper};
+use super::{log2, PivotChooser, QuickSort};
+use crate::stats::{self, Scratch};
+
+/// Slices shorter than this are sorted on one thread by the parallel sorts.
+///
+/// Starting a thread takes tens of microseconds, which is about as long as it
+/// takes to sort this many small items.
+const PARALLEL_THRESHOLD: usize = 1 << 13;
+
+/// How many threads to sort `len` items on, for a `threads` option where `0`
+/// means one per CPU.
+///
+/// Short slices only ever use one, so they skip asking how many CPUs there are,
+/// which can mean reading files on some platforms.
+///
+/// So does a thread that `stats::measure` or `trace::trace` is watching, since
+/// they only see the work done on that thread.
+fn threads(threads: usize, len: usize) -> usize {
+    match threads {
+        _ if len < PARALLEL_THRESHOLD || stats::watched() => 1,
+        0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        threads => threads,
+    }
+}
+
+/// Runs `a` on a new thread and `b` on this one, and returns once both are done.
+///
+/// If either panics, this waits for the other, and then panics with the
+/// same payload.
+fn join<A, B>(a: A, b: B)
+where
+    A: FnOnce() + Send,
+    B: FnOnce(),
+{
+    thread::scope(|scope| {
+        let a = scope.spawn(a);
+        b();
+        if let Err(payload) = a.join() {
+            panic::resume_unwind(payload);
+        }
+    });
+}
+
+pub fn par_merge_sort<T: Ord + Send>(items: &mut [T]) {
+    par_merge_sort_by(items, T::cmp);
+}
+
+/// Merge sort, with each half sorted on its own thread.
+///
+/// This uses the default [`ParMergeSort`] options, with one thread per CPU.
+/// See there for the details.
+pub fn par_merge_sort_by<T, F>(items: &mut [T], compare: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    ParMergeSort::default().sort_by(items, compare);
+}
+
+/// Options for `par_merge_sort`.
+///
+/// This splits the items in half, and sorts each half on its own thread, until
+/// it runs out of threads or the halves get short. Then it merges them back
+/// together on one thread. Like `merge_sort`, this is stable.
+///
+/// The `N/2` item buffer that `merge_sort` uses is split between the halves,
+/// so this needs no more memory than that.
+///
+/// ```
+/// use algos::ParMergeSort;
+///
+/// let mut items: Vec<u32> = (0..100_000).rev().collect();
+/// ParMergeSort { threads: 4 }.sort(&mut items);
+/// assert!(items.windows(2).all(|w| w[0] <= w[1]));
+/// ```
+///
+/// Inside `stats::measure` or `trace::trace`, this sorts on one thread, so
+/// that all of the work is counted and recorded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParMergeSort {
+    /// How many threads to sort on, including this one.
+    ///
+    /// `0` uses one per CPU, from `std::thread::available_parallelism`.
+    pub threads: usize,
+}
+
+impl ParMergeSort {
+    pub fn sort<T: Ord + Send>(&self, items: &mut [T]) {
+        self.sort_by(items, T::cmp);
+    }
+
+    pub fn sort_by<T, F>(&self, items: &mut [T], compare: F)
+    where
+        T: Send,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        // Zero sized items all look the same, so there's nothing to sort.
+        if core::mem::size_of::<T>() == 0 {
+            return;
+        }
+
+        let mut scratch: Vec<T> = Vec::with_capacity(items.len() / 2);
+        let _scratch = Scratch::new::<T>(scratch.capacity());
+        let is_less = |a: &T, b: &T| compare(a, b) == Ordering::Less;
+
+        par_merge_helper(
+            scratch.spare_capacity_mut(),
+            items,
+            &is_less,
+            threads(self.threads, items.len()),
+        );
+    }
+
+    pub fn sort_by_key<T, K, F>(&self, items: &mut [T], f: F)
+    where
+        T: Send,
+        K: Ord,
+        F: Fn(&T) -> K + Sync,
+    {
+        self.sort_by(items, |a, b| f(a).cmp(&f(b)));
+    }
+}
+
+/// Sorts `items` on up to `threads` threads, using `scratch`, which must have
+/// room for half of them.
+fn par_merge_helper<T, F>(
+    scratch: &mut [MaybeUninit<T>],
+    items: &mut [T],
+    is_less: &F,
+    threads: usize,
+) where
+    T: Send,
+    F: Fn(&T, &T) -> bool + Sync,
+{
+    if threads < 2 || items.len() < PARALLEL_THRESHOLD {
+        merge_helper(scratch, items, &mut |a: &T, b: &T| is_less(a, b), &mut ());
+        return;
+    }
+
+    // Each half gets its share of the buffer, which is all that its merges need.
+    let mid = items.len() / 2;
+    let (left, right) = items.split_at_mut(mid);
+    let (left_scratch, right_scratch) = scratch.split_at_mut(mid / 2);
+    join(
+        || par_merge_helper(left_scratch, left, is_less, threads / 2),
+        || par_merge_helper(right_scratch, right, is_less, threads - threads / 2),
+    );
+
+    merge(
+        scratch,
+        items,
+        mid,
+        &mut |a: &T, b: &T| is_less(a, b),
+        &mut (),
+    );
+}
+
+pub fn par_quick_sort<T: Ord + Send>(items: &mut [T]) {
+    par_quick_sort_by(items, T::cmp);
+}
+
+/// Quicksort, with each partition sorted on its own thread.
+///
+/// This uses the default [`ParQuickSort`] options, with one thread per CPU.
+/// See there for the details.
+pub fn par_quick_sort_by<T, F>(items: &mut [T], compare: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    ParQuickSort::default().sort_by(items, compare);
+}
+
+/// Options for `par_quick_sort`.
+///
+/// This partitions the items, and sorts each side on its own thread, until it
+/// runs out of threads or the sides get short. Then each side is sorted with
+/// `quick_sort`, using the same options.
+///
+/// Each partition is done on one thread, so the first one, over all of the
+/// items, limits how much faster this can get.
+///
+/// ```
+/// use algos::{ParQuickSort, PartitionScheme, QuickSort};
+///
+/// let mut items: Vec<u32> = (0..100_000).map(|i| i * 7919 % 100_000).collect();
+/// ParQuickSort {
+///     threads: 4,
+///     quick_sort: QuickSort {
+///         partition: PartitionScheme::Block,
+///         ..QuickSort::default()
+///     },
+/// }
+/// .sort(&mut items);
+/// assert!(items.windows(2).all(|w| w[0] <= w[1]));
+/// ```
+///
+/// Inside `stats::measure` or `trace::trace`, this sorts on one thread, so
+/// that all of the work is counted and recorded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParQuickSort {
+    /// How many threads to sort on, including this one.
+    ///
+    /// `0` uses one per CPU, from `std::thread::available_parallelism`.
+    pub threads: usize,
+
+    /// How to pick pivots and partition around them.
+    pub quick_sort: QuickSort,
+}
+
+impl ParQuickSort {
+    pub fn sort<T: Ord + Send>(&self, items: &mut [T]) {
+        self.sort_by(items, T::cmp);
+    }
+
+    pub fn sort_by<T, F>(&self, items: &mut [T], compare: F)
+    where
+        T: Send,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        let depth_limit = 2 * log2(items.len());
+        self.par_quick_helper(
+            items,
+            &compare,
+            depth_limit,
+            threads(self.threads, items.len()),
+            0,
+        );
+    }
+
+    pub fn sort_by_key<T, K, F>(&self, items: &mut [T], f: F)
+    where
+        T: Send,
+        K: Ord,
+        F: Fn(&T) -> K + Sync,
+    {
+        self.sort_by(items, |a, b| f(a).cmp(&f(b)));
+    }
+
+    fn par_quick_helper<T, F>(
+        &self,
+        items: &mut [T],
+        compare: &F,
+        depth_limit: usize,
+        threads: usize,
+        task: u64,
+    ) where
+        T: Send,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        // Each subtask picks its own pivots. Subtasks are numbered like the
+        // nodes of a binary heap, so each gets its own random pivots. They're
+        // still reproducible, since the same items are always split the same way.
+        let mut pivots = PivotChooser::for_task(self.quick_sort.pivot, task);
+        let mut compare_mut = |a: &T, b: &T| compare(a, b);
+
+        // Running out of depth falls back to heap sort, which `quick_helper` handles.
+        if threads < 2 || items.len() < PARALLEL_THRESHOLD || depth_limit == 0 {
+            self.quick_sort
+                .quick_helper(items, &mut pivots, &mut compare_mut, depth_limit);
+            return;
+        }
+
+        let (lo, hi) = self
+            .quick_sort
+            .partition_step(items, &mut pivots, &mut compare_mut);
+        let (left, rest) = items.split_at_mut(lo);
+        let right = &mut rest[hi - lo..];
+        // Only seeds need these to differ, so wrapping around is harmless
+        let left_task = task.wrapping_mul(2).wrapping_add(1);
+        let right_task = left_task.wrapping_add(1);
+        join(
+            || self.par_quick_helper(left, compare, depth_limit - 1, threads / 2, left_task),
+            || {
+                let threads = threads - threads / 2;
+                self.par_quick_helper(right, compare, depth_limit - 1, threads, right_task)
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PartitionScheme, PivotStrategy};
+    use core::sync::atomic::{self, AtomicUsize};
+    use std::panic::AssertUnwindSafe;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    type Sort<T> = Box<dyn Fn(&mut [T], &(dyn Fn(&T, &T) -> Ordering + Sync))>;
+
+    /// Every parallel sort, with a range of options, and how many threads it uses.
+    fn sorts<T: Send + 'static>() -> Vec<(String, Sort<T>)> {
+        let mut sorts: Vec<(String, Sort<T>)> = vec![];
+        for threads in [1, 2, 3, 8] {
+            sorts.push((
+                format!("par-merge/{}", threads),
+                Box::new(move |v, c| ParMergeSort { threads }.sort_by(v, c)),
+            ));
+
+            for partition in [
+                PartitionScheme::Lomuto,
+                PartitionScheme::ThreeWay,
+                PartitionScheme::Block,
+            ] {
+                let sort = ParQuickSort {
+                    threads,
+                    quick_sort: QuickSort {
+                        pivot: PivotStrategy::Random { seed: 0x5EED },
+                        partition,
+                    },
+                };
+                sorts.push((
+                    format!("par-quick-{:?}/{}", partition, threads),
+                    Box::new(move |v, c| sort.sort_by(v, c)),
+                ));
+            }
+        }
+
+        sorts
+    }
+
+    #[test]
+    fn check_par_sorts() {
+        let mut rng = SmallRng::seed_from_u64(0x5EED);
+        for len in [0, 1, 2, 100, PARALLEL_THRESHOLD, 40_000] {
+            let random: Vec<u32> = (0..len).map(|_| rng.gen()).collect();
+            let few_keys: Vec<u32> = (0..len).map(|_| rng.gen_range(0, 4)).collect();
+            let sorted: Vec<u32> = (0..len as u32).collect();
+            let reversed: Vec<u32> = (0..len as u32).rev().collect();
+
+            for original in [random, few_keys, sorted, reversed] {
+                let mut expected = original.clone();
+                expected.sort_unstable();
+
+                for (name, sort) in sorts() {
+                    let mut items = original.clone();
+                    sort(&mut items, &u32::cmp);
+                    assert!(items == expected, "{} failed on {} items", name, len);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_par_quick_sort_subtasks_pick_different_pivots() {
+        let items: Vec<u32> = (0..1_000).collect();
+        let pivots = |task| {
+            let mut pivots = PivotChooser::for_task(PivotStrategy::Random { seed: 0x5EED }, task);
+            let mut compare = u32::cmp;
+            (0..10)
+                .map(|_| pivots.choose(&items, &mut compare))
+                .collect::<Vec<_>>()
+        };
+
+        let mut root = PivotChooser::new(PivotStrategy::Random { seed: 0x5EED });
+        let expected: Vec<usize> = (0..10)
+            .map(|_| root.choose(&items, &mut u32::cmp))
+            .collect();
+        assert_eq!(pivots(0), expected);
+
+        let (left, right) = (pivots(1), pivots(2));
+        assert_ne!(left, expected);
+        assert_ne!(right, expected);
+        assert_ne!(left, right);
+    }
+
+    #[test]
+    fn check_par_merge_sort_is_stable() {
+        let mut rng = SmallRng::seed_from_u64(0x5EED);
+        let mut items: Vec<(u8, usize)> = (0..100_000).map(|i| (rng.gen_range(0, 10), i)).collect();
+        let mut expected = items.clone();
+        expected.sort_by_key(|(k, _)| *k);
+
+        ParMergeSort { threads: 8 }.sort_by_key(&mut items, |(k, _)| *k);
+        assert!(items == expected);
+    }
+
+    #[test]
+    fn check_par_sorts_panic_safety() {
+        let original: Vec<u32> = (0..20_000).map(|i| i * 7919 % 20_000).collect();
+
+        for (name, sort) in sorts::<Box<u32>>() {
+            let count = AtomicUsize::new(0);
+            sort(
+                &mut original.iter().copied().map(Box::new).collect::<Vec<_>>(),
+                &|a, b| {
+                    count.fetch_add(1, atomic::Ordering::Relaxed);
+                    a.cmp(b)
+                },
+            );
+            let comparisons = count.into_inner();
+
+            for fuse in [0, 1_000, comparisons / 2, comparisons - 1] {
+                // Boxes are freed twice if the sort duplicates one.
+                let mut items: Vec<Box<u32>> = original.iter().copied().map(Box::new).collect();
+                let count = AtomicUsize::new(0);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    sort(&mut items, &|a, b| {
+                        if count.fetch_add(1, atomic::Ordering::Relaxed) == fuse {
+                            panic::resume_unwind(Box::new("boom"));
+                        }
+                        a.cmp(b)
+                    })
+                }));
+
+                // Threads compare in a different order each time, so they might
+                // not get as far as last time.
+                assert!(
+                    result.is_err() || fuse >= comparisons / 2,
+                    "{} at {}",
+                    name,
+                    fuse
+                );
+
+                let mut values: Vec<u32> = items.iter().map(|x| **x).collect();
+                values.sort_unstable();
+                assert!(values.iter().copied().eq(0..20_000), "{} at {}", name, fuse);
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use core::cmp::Ordering;
+
+use super::merge_sort_by;
+use crate::stats;
+
+pub fn argsort<T: Ord>(items: &[T]) -> Vec<usize> {
+    argsort_by(items, T::cmp)
+}
+
+/// Returns the indexes of `items` in sorted order, and leaves `items` alone.
+///
+/// `items[argsort(items)[i]]` is the `i`th smallest item. That's the order
+/// that [`apply_permutation`] puts them in, which can reorder other slices in
+/// the same way too.
+///
+/// This uses `merge_sort`, so equal items stay in the order that they were
+/// in. Use [`argsort_with`] to pick another sort.
+///
+/// ```
+/// use algos::argsort;
+///
+/// let scores = [30, 10, 20, 10];
+/// assert_eq!(argsort(&scores), [1, 3, 2, 0]);
+/// ```
+pub fn argsort_by<T, F>(items: &[T], compare: F) -> Vec<usize>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    argsort_with(items, compare, |indexes, compare| {
+        merge_sort_by(indexes, compare)
+    })
+}
+
+pub fn argsort_by_key<T, K, F>(items: &[T], mut f: F) -> Vec<usize>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    argsort_by(items, |a, b| f(a).cmp(&f(b)))
+}
+
+/// Like [`argsort_by`], but `sort` does the sorting.
+///
+/// `sort` gets the indexes, and a comparator that compares the items at them.
+/// It should pass that comparator on to a `_by` sort. If that sort isn't
+/// stable, equal items may come out in any order.
+///
+/// ```
+/// use algos::{argsort_with, quick_sort_by};
+///
+/// let names = ["carol", "alice", "bob"];
+/// let order = argsort_with(&names, |a, b| a.cmp(b), |indexes, compare| {
+///     quick_sort_by(indexes, compare)
+/// });
+/// assert_eq!(order, [1, 2, 0]);
+/// ```
+pub fn argsort_with<T, C, S>(items: &[T], mut compare: C, sort: S) -> Vec<usize>
+where
+    C: FnMut(&T, &T) -> Ordering,
+    S: FnOnce(&mut [usize], &mut dyn FnMut(&usize, &usize) -> Ordering),
+{
+    let mut indexes: Vec<usize> = (0..items.len()).collect();
+    sort(&mut indexes, &mut |&a, &b| compare(&items[a], &items[b]));
+    indexes
+}
+
+/// Reorders `items` so that `items[i]` is what was at `items[perm[i]]`.
+///
+/// This is the order that [`argsort`] returns, so applying it sorts the
+/// items that it came from.
+///
+/// Items are moved by swapping them around each cycle of the permutation,
+/// so they don't need to be `Clone`. This takes `O(N)` time, and a flag per
+/// item to mark which slots are done, which leaves `perm` alone.
+///
+/// ```
+/// use algos::{apply_permutation, argsort};
+///
+/// let ages = [31, 25, 40];
+/// let mut names = vec!["carol".to_string(), "alice".to_string(), "bob".to_string()];
+/// apply_permutation(&mut names, &argsort(&ages));
+/// assert_eq!(names, ["alice", "carol", "bob"]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `perm` isn't a permutation of `0..items.len()`. `items` is left
+/// as it was.
+pub fn apply_permutation<T>(items: &mut [T], perm: &[usize]) {
+    assert_eq!(items.len(), perm.len(), "permutation has the wrong length");
+
+    let mut todo = check_permutation(perm);
+
+    // Each swap puts one more item in its final slot, and moves the item that
+    // was there on to the next slot around the cycle, until it reaches the slot
+    // that the cycle started from, which is where it belongs.
+    for start in 0..perm.len() {
+        if !todo[start] {
+            continue;
+        }
+
+        let mut slot = start;
+        loop {
+            todo[slot] = false;
+            let from = perm[slot];
+            if from == start {
+                break;
+            }
+            stats::swap(items, slot, from);
+            slot = from;
+        }
+    }
+}
+
+/// Returns the permutation that undoes `perm`.
+///
+/// If `perm` moves the item at `j` to `i`, the inverse moves it back, so
+/// `invert_permutation(perm)[perm[i]] == i`. For the result of [`argsort`],
+/// that's the rank of each item.
+///
+/// ```
+/// use algos::{argsort, invert_permutation};
+///
+/// let scores = [30, 10, 20];
+/// assert_eq!(invert_permutation(&argsort(&scores)), [2, 0, 1]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `perm` isn't a permutation of `0..perm.len()`.
+pub fn invert_permutation(perm: &[usize]) -> Vec<usize> {
+    check_permutation(perm);
+
+    let mut inverse = vec![0; perm.len()];
+    for (i, &j) in perm.iter().enumerate() {
+        inverse[j] = i;
+    }
+    inverse
+}
+
+/// Panics unless `perm` has each of `0..perm.len()` exactly once.
+///
+/// Returns a flag for each index, which are all set.
+fn check_permutation(perm: &[usize]) -> Vec<bool> {
+    let mut seen = vec![false; perm.len()];
+    for &i in perm {
+        assert!(
+            i < perm.len() && !seen[i],
+            "not a permutation: {} is out of range, or repeated",
+            i
+        );
+        seen[i] = true;
+    }
+    seen
+}
+
+/// A column of items that can be reordered by a permutation.
+///
+/// [`sort_parallel`] takes these, so that each column can hold a different
+/// type.
+pub trait Permute {
+    /// How many items are in the column.
+    fn len(&self) -> usize;
+
+    /// Whether the column has no items.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reorders the column with [`apply_permutation`].
+    fn permute(&mut self, perm: &[usize]);
+}
+
+impl<T> Permute for Vec<T> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn permute(&mut self, perm: &[usize]) {
+        apply_permutation(self, perm);
+    }
+}
+
+impl<T, const N: usize> Permute for [T; N] {
+    fn len(&self) -> usize {
+        N
+    }
+
+    fn permute(&mut self, perm: &[usize]) {
+        apply_permutation(self, perm);
+    }
+}
+
+/// Sorts `keys`, and reorders each of `values` in the same way.
+///
+/// This is for parallel arrays, where the fields of each record are stored in
+/// their own column, and the `i`th record is the `i`th item of every column.
+/// Records with equal keys stay in the order that they were in.
+///
+/// ```
+/// use algos::sort_parallel;
+///
+/// let mut ages = vec![31, 25, 40];
+/// let mut names = vec!["carol", "alice", "bob"];
+/// let mut heights = vec![1.62, 1.75, 1.80];
+/// sort_parallel(&mut ages, &mut [&mut names, &mut heights]);
+///
+/// assert_eq!(ages, [25, 31, 40]);
+/// assert_eq!(names, ["alice", "carol", "bob"]);
+/// assert_eq!(heights, [1.75, 1.62, 1.80]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if any column is a different length than `keys`. Nothing is
+/// reordered if it does.
+pub fn sort_parallel<K: Ord>(keys: &mut [K], values: &mut [&mut dyn Permute]) {
+    for column in values.iter() {
+        assert_eq!(keys.len(), column.len(), "columns have different lengths");
+    }
+
+    let perm = argsort(keys);
+    apply_permutation(keys, &perm);
+    for column in values.iter_mut() {
+        column.permute(&perm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quick_sort_by;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    #[test]
+    fn check_argsort_sorts() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for len in [0, 1, 2, 10, 1_000] {
+            let items: Vec<u32> = (0..len).map(|_| rng.gen_range(0, 100)).collect();
+            let mut expected = items.clone();
+            expected.sort();
+
+            let sorted: Vec<u32> = argsort(&items).into_iter().map(|i| items[i]).collect();
+            assert_eq!(sorted, expected);
+
+            let order = argsort_with(&items, u32::cmp, |v, c| quick_sort_by(v, c));
+            let sorted: Vec<u32> = order.into_iter().map(|i| items[i]).collect();
+            assert_eq!(sorted, expected);
+
+            let mut moved = items.clone();
+            apply_permutation(&mut moved, &argsort_by_key(&items, |&x| x));
+            assert_eq!(moved, expected);
+        }
+    }
+
+    #[test]
+    fn check_argsort_is_stable() {
+        let items = [2, 1, 2, 1, 2, 1];
+        assert_eq!(argsort(&items), [1, 3, 5, 0, 2, 4]);
+        assert_eq!(argsort_by(&items, |a, b| b.cmp(a)), [0, 2, 4, 1, 3, 5]);
+    }
+
+    #[test]
+    fn check_apply_and_invert_permutation() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for len in [0, 1, 2, 10, 1_000] {
+            let mut perm: Vec<usize> = (0..len).collect();
+            perm.shuffle(&mut rng);
+
+            // Boxes aren't `Copy`, and are freed twice if one is duplicated
+            let mut items: Vec<Box<usize>> = (0..len).map(Box::new).collect();
+            apply_permutation(&mut items, &perm);
+            assert!(items.iter().map(|x| **x).eq(perm.iter().copied()));
+
+            // Undoing it puts everything back
+            apply_permutation(&mut items, &invert_permutation(&perm));
+            assert!(items.iter().map(|x| **x).eq(0..len));
+
+            let inverse = invert_permutation(&perm);
+            assert!((0..len).all(|i| inverse[perm[i]] == i));
+            assert_eq!(invert_permutation(&inverse), perm);
+        }
+    }
+
+    #[test]
+    fn check_bad_permutations_panic() {
+        for perm in [&[0, 0, 1][..], &[0, 1, 3], &[0, 1]] {
+            let mut items = vec![1, 2, 3];
+            let result =
+                panic::catch_unwind(AssertUnwindSafe(|| apply_permutation(&mut items, perm)));
+            assert!(result.is_err(), "{:?}", perm);
+            assert_eq!(items, [1, 2, 3]);
+        }
+
+        assert!(panic::catch_unwind(|| invert_permutation(&[1, 1])).is_err());
+    }
+
+    #[test]
+    fn check_sort_parallel() {
+        let mut ids = vec![3, 1, 2, 1];
+        let mut names = vec!["c", "a", "b", "a2"];
+        let mut flags = [true, false, true, false];
+        sort_parallel(&mut ids, &mut [&mut names, &mut flags]);
+
+        assert_eq!(ids, [1, 1, 2, 3]);
+        assert_eq!(names, ["a", "a2", "b", "c"]);
+        assert_eq!(flags, [false, false, true, true]);
+    }
+
+    #[test]
+    #[should_panic(expected = "columns have different lengths")]
+    fn check_sort_parallel_lengths() {
+        sort_parallel(&mut [2, 1], &mut [&mut vec!['a', 'b', 'c']]);
+    }
+}
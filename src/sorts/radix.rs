@@ -0,0 +1,408 @@
+use crate::stats::{self, Scratch};
+use crate::trace::Recursion;
+
+/// A key that can be sorted by `radix_sort`, one byte at a time.
+///
+/// The key is treated as a `BYTES` long unsigned number, and sorting those
+/// numbers must give the same order as sorting the keys.
+pub trait RadixKey: Copy {
+    /// How many bytes `radix_byte()` can return.
+    const BYTES: usize;
+
+    /// Returns the `byte`th least significant byte of the key.
+    fn radix_byte(&self, byte: usize) -> u8;
+}
+
+macro_rules! impl_radix_key {
+    ($($ty:ty => $unsigned:ty, $to_bits:expr;)+) => {
+        $(
+            impl RadixKey for $ty {
+                const BYTES: usize = core::mem::size_of::<$ty>();
+
+                #[inline]
+                fn radix_byte(&self, byte: usize) -> u8 {
+                    let to_bits: fn($ty) -> $unsigned = $to_bits;
+                    (to_bits(*self) >> (8 * byte)) as u8
+                }
+            }
+        )+
+    };
+}
+
+impl_radix_key! {
+    u8 => u8, |x| x;
+    u16 => u16, |x| x;
+    u32 => u32, |x| x;
+    u64 => u64, |x| x;
+    u128 => u128, |x| x;
+    usize => usize, |x| x;
+
+    // Two's complement puts negative numbers after positive ones.
+    // Flipping the sign bit moves them in front, and keeps everything else in order.
+    i8 => u8, |x| (x as u8) ^ (1 << 7);
+    i16 => u16, |x| (x as u16) ^ (1 << 15);
+    i32 => u32, |x| (x as u32) ^ (1 << 31);
+    i64 => u64, |x| (x as u64) ^ (1 << 63);
+    i128 => u128, |x| (x as u128) ^ (1 << 127);
+    isize => usize, |x| (x as usize) ^ (1 << (usize::BITS - 1));
+
+    // IEEE 754 floats are sign-magnitude.
+    // Positive floats are already in order, so flip the sign bit to move them after the negative ones.
+    // Negative floats are in reverse order, so flip every bit to reverse them again.
+    // This is the IEEE 754 totalOrder: -NaN < -inf < ... < -0.0 < 0.0 < ... < inf < NaN
+    f32 => u32, |x| {
+        let bits = x.to_bits();
+        if bits >> 31 == 1 { !bits } else { bits ^ (1 << 31) }
+    };
+    f64 => u64, |x| {
+        let bits = x.to_bits();
+        if bits >> 63 == 1 { !bits } else { bits ^ (1 << 63) }
+    };
+}
+
+/// Returns the number that `key`'s radix bytes spell out.
+///
+/// # Panics
+///
+/// Panics if the key is too long to fit in a `u128`.
+pub(super) fn radix_value<K: RadixKey>(key: &K) -> u128 {
+    assert!(K::BYTES <= 16, "{} byte keys don't fit in a u128", K::BYTES);
+    (0..K::BYTES).fold(0, |value, byte| {
+        value | (key.radix_byte(byte) as u128) << (8 * byte)
+    })
+}
+
+/// Sorts `items` in `O(N * K)` time, where `K` is the size of the key in bytes.
+///
+/// This is a least significant digit radix sort. It never compares items:
+/// instead it does a stable counting sort on each byte of the key, starting
+/// from the least significant one.
+/// Floats are sorted by their IEEE 754 total order.
+pub fn radix_sort<T: RadixKey>(items: &mut [T]) {
+    lsd_radix_sort(items, |item| *item);
+}
+
+/// Sorts `items` by the key `f` extracts from each element, using [`radix_sort`].
+///
+/// The key function is called exactly once per element, and items with equal keys
+/// keep their original order.
+pub fn radix_sort_by_key<T, K, F>(items: &mut [T], f: F)
+where
+    K: RadixKey,
+    F: FnMut(&T) -> K,
+{
+    super::sort_by_cached_key_with(items, f, |keys| lsd_radix_sort(keys, |(k, _i)| *k));
+}
+
+fn lsd_radix_sort<T, K, F>(items: &mut [T], key: F)
+where
+    T: Copy,
+    K: RadixKey,
+    F: Fn(&T) -> K,
+{
+    if items.len() < 2 {
+        return;
+    }
+
+    // Count how many of each byte we have, for every byte of the key, in one pass.
+    let mut counts = vec![[0_usize; 256]; K::BYTES];
+    let _counts_scratch = Scratch::new::<[usize; 256]>(counts.len());
+    for item in items.iter() {
+        let k = key(item);
+        for (byte, count) in counts.iter_mut().enumerate() {
+            count[k.radix_byte(byte) as usize] += 1;
+        }
+    }
+
+    let mut scratch: Vec<T> = items.to_vec();
+    let _scratch = Scratch::new::<T>(scratch.len());
+    stats::writes(scratch.len());
+    let mut sorted_in_scratch = false;
+
+    for (byte, count) in counts.iter().enumerate() {
+        // If every item has the same byte here, this pass wouldn't move anything.
+        // This is common for the high bytes of small numbers.
+        if count.contains(&items.len()) {
+            continue;
+        }
+
+        let (src, dst): (&[T], &mut [T]) = if sorted_in_scratch {
+            (&scratch, items)
+        } else {
+            (items, &mut scratch)
+        };
+
+        // Turn counts into the starting offset of each byte's bucket
+        let mut offsets = [0_usize; 256];
+        for b in 1..256 {
+            offsets[b] = offsets[b - 1] + count[b - 1];
+        }
+
+        // Walking `src` in order and appending to buckets keeps this pass stable.
+        for item in src.iter() {
+            let b = key(item).radix_byte(byte) as usize;
+            dst[offsets[b]] = *item;
+            offsets[b] += 1;
+        }
+        // Traces see the whole pass written in order, which replays the same.
+        stats::wrote(dst);
+
+        sorted_in_scratch = !sorted_in_scratch;
+    }
+
+    if sorted_in_scratch {
+        items.copy_from_slice(&scratch);
+        stats::wrote(items);
+    }
+}
+
+/// Buckets this short are handed off to `insertion_sort` by `msd_radix_sort`.
+const MSD_INSERTION_SORT_THRESHOLD: usize = 16;
+
+/// Sorts byte strings lexicographically, one byte at a time.
+///
+/// This is a most significant digit radix sort, in the style of American flag
+/// sort: the items are bucketed in place by their first byte, then each bucket is
+/// sorted by the second byte, and so on. Shorter strings sort before any longer
+/// string that they are a prefix of.
+///
+/// ```
+/// use algos::msd_radix_sort;
+///
+/// let mut words = vec!["banana", "apple", "app", "cherry"];
+/// msd_radix_sort(&mut words);
+/// assert_eq!(words, ["app", "apple", "banana", "cherry"]);
+/// ```
+pub fn msd_radix_sort<S: AsRef<[u8]>>(items: &mut [S]) {
+    msd_helper(items, 0);
+}
+
+/// Returns which bucket `item` goes in, when sorting on `depth`.
+///
+/// Strings that have already ended go in bucket 0, before every byte.
+pub(super) fn msd_bucket(item: &[u8], depth: usize) -> usize {
+    item.get(depth).map(|b| *b as usize + 1).unwrap_or(0)
+}
+
+fn msd_helper<S: AsRef<[u8]>>(mut items: &mut [S], mut depth: usize) {
+    loop {
+        let _recursion = Recursion::enter(items);
+
+        if items.len() <= MSD_INSERTION_SORT_THRESHOLD {
+            sort_suffixes(items, depth);
+            return;
+        }
+
+        let mut counts = [0_usize; 257];
+        for item in items.iter() {
+            counts[msd_bucket(item.as_ref(), depth)] += 1;
+        }
+
+        // When every string has the same byte here, they probably share more.
+        // Skipping all of it at once saves a pass over them for each byte.
+        if counts[1..].contains(&items.len()) {
+            depth += 1 + common_prefix_len(items, depth + 1);
+            continue;
+        }
+
+        // `starts[b]..ends[b]` is where bucket `b` will live
+        let mut starts = [0_usize; 257];
+        for b in 1..257 {
+            starts[b] = starts[b - 1] + counts[b - 1];
+        }
+        let mut ends = [0_usize; 257];
+        for b in 0..257 {
+            ends[b] = starts[b] + counts[b];
+        }
+
+        // Permute the items into their buckets in place.
+        // `next[b]` is the first slot in bucket `b` that doesn't hold one of its items yet.
+        // We look at the item there and swap it into its own bucket, until the slot
+        // receives an item that belongs in it.
+        let mut next = starts;
+        for b in 0..257 {
+            while next[b] < ends[b] {
+                let dest = msd_bucket(items[next[b]].as_ref(), depth);
+                if dest == b {
+                    next[b] += 1;
+                } else {
+                    stats::swap(items, next[b], next[dest]);
+                    next[dest] += 1;
+                }
+            }
+        }
+
+        // Strings in bucket 0 have ended and are all equal, so they're done.
+        // Everything else needs sorting on the next byte. We recurse into the
+        // smaller buckets, and loop on the largest, so each recurse has at most
+        // half of the items, and the stack stays `O(lg N)` deep however long
+        // the strings are.
+        let largest = (1..257).max_by_key(|&b| counts[b]).unwrap();
+        for b in 1..257 {
+            if b != largest && counts[b] > 1 {
+                msd_helper(&mut items[starts[b]..ends[b]], depth + 1);
+            }
+        }
+
+        if counts[largest] <= 1 {
+            return;
+        }
+        let all = items;
+        items = &mut all[starts[largest]..ends[largest]];
+        depth += 1;
+    }
+}
+
+/// How many bytes, starting at `depth`, every one of `items` has in common.
+pub(super) fn common_prefix_len<S: AsRef<[u8]>>(items: &[S], depth: usize) -> usize {
+    let first = items[0].as_ref().get(depth..).unwrap_or_default();
+    items[1..].iter().fold(first.len(), |len, item| {
+        let item = item.as_ref().get(depth..).unwrap_or_default();
+        first[..len]
+            .iter()
+            .zip(item)
+            .take_while(|(a, b)| a == b)
+            .count()
+    })
+}
+
+/// Sorts byte strings that all share the same first `depth` bytes, with
+/// `insertion_sort`, by comparing just the rest of them.
+pub(super) fn sort_suffixes<S: AsRef<[u8]>>(items: &mut [S], depth: usize) {
+    super::insertion_sort_by(
+        items,
+        stats::counted(|a: &S, b: &S| {
+            let a = a.as_ref().get(depth..).unwrap_or_default();
+            let b = b.as_ref().get(depth..).unwrap_or_default();
+            a.cmp(b)
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    macro_rules! make_int_test {
+        ($(fn $test_name:ident => $ty:ty),+) => {
+            $(
+                #[test]
+                fn $test_name () {
+                    let mut items: Vec<$ty> = vec![<$ty>::MAX, <$ty>::MIN, 0, 1, 2, 100, <$ty>::MAX - 1];
+                    let mut rng = SmallRng::seed_from_u64(0);
+                    items.extend((0..1_000).map(|_| rng.gen::<$ty>()));
+
+                    let mut expected = items.clone();
+                    expected.sort_unstable();
+
+                    radix_sort(&mut items);
+                    assert_eq!(items, expected);
+                }
+            )+
+        }
+    }
+
+    make_int_test! {
+        fn check_radix_sort_u8 => u8,
+        fn check_radix_sort_u16 => u16,
+        fn check_radix_sort_u32 => u32,
+        fn check_radix_sort_u64 => u64,
+        fn check_radix_sort_u128 => u128,
+        fn check_radix_sort_usize => usize,
+        fn check_radix_sort_i8 => i8,
+        fn check_radix_sort_i16 => i16,
+        fn check_radix_sort_i32 => i32,
+        fn check_radix_sort_i64 => i64,
+        fn check_radix_sort_i128 => i128,
+        fn check_radix_sort_isize => isize
+    }
+
+    #[test]
+    fn check_radix_sort_floats() {
+        let mut items: Vec<f64> = vec![
+            f64::NAN,
+            1.5,
+            -0.0,
+            f64::INFINITY,
+            0.0,
+            -f64::NAN,
+            -2.5,
+            f64::NEG_INFINITY,
+            f64::MIN_POSITIVE,
+            -1.5,
+        ];
+        let mut expected = items.clone();
+        expected.sort_by(f64::total_cmp);
+
+        radix_sort(&mut items);
+        let bits = |xs: &[f64]| xs.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&items), bits(&expected));
+
+        let mut items: Vec<f32> = vec![3.0, -1.0, 0.5, -0.0, 0.0, -7.25];
+        radix_sort(&mut items);
+        assert_eq!(items, [-7.25, -1.0, -0.0, 0.0, 0.5, 3.0]);
+    }
+
+    #[test]
+    fn check_radix_sort_by_key_is_stable() {
+        let mut items: Vec<(i16, usize)> = (0..500).map(|i| ((i % 7) as i16 - 3, i)).collect();
+        items.shuffle(&mut thread_rng());
+        let mut expected = items.clone();
+        expected.sort_by_key(|(k, _i)| *k);
+
+        radix_sort_by_key(&mut items, |(k, _i)| *k);
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn check_msd_radix_sort() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut items: Vec<Vec<u8>> = (0..2_000)
+            .map(|_| {
+                let len = rng.gen_range(0, 8);
+                // A small alphabet gives us lots of shared prefixes.
+                (0..len).map(|_| rng.gen_range(b'a', b'e')).collect()
+            })
+            .collect();
+
+        let mut expected = items.clone();
+        expected.sort();
+
+        msd_radix_sort(&mut items);
+        assert_eq!(items, expected);
+
+        let mut items: Vec<String> = ["b", "", "ab", "a", "ba", "aa", "", "abc"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        msd_radix_sort(&mut items);
+        assert_eq!(items, ["", "", "a", "aa", "ab", "abc", "b", "ba"]);
+
+        let mut items: Vec<&[u8]> = vec![b"\xff", b"\x00", b"", b"\x00\x00"];
+        msd_radix_sort(&mut items);
+        assert_eq!(items, [&b""[..], b"\x00", b"\x00\x00", b"\xff"]);
+    }
+
+    #[test]
+    fn check_msd_radix_sort_long_prefixes() {
+        // A byte at a time, these would need a level of recursion for each
+        // byte, and overflow the stack
+        let prefix = "x".repeat(10_000);
+        let mut items: Vec<String> = (0..32).rev().map(|i| format!("{}{}", prefix, i)).collect();
+        let mut expected = items.clone();
+        expected.sort();
+        msd_radix_sort(&mut items);
+        assert_eq!(items, expected);
+
+        // One string ends at each byte, so the strings never all share the next one
+        let mut items: Vec<String> = (0..5_000).rev().map(|len| "x".repeat(len)).collect();
+        let mut expected = items.clone();
+        expected.sort();
+        msd_radix_sort(&mut items);
+        assert_eq!(items, expected);
+    }
+}
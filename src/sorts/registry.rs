@@ -0,0 +1,407 @@
+use core::fmt;
+
+use super::radix::radix_value;
+use super::*;
+
+/// A sorting algorithm, and the facts about it that matter when picking one.
+///
+/// Every sort in this crate is listed in [`comparison_sorters`],
+/// [`key_sorters`] or [`string_sorters`], so tests, benches and tools can run
+/// all of them without listing them by hand.
+///
+/// If comparing two items panics, every sort leaves `items` holding the same
+/// items that it started with, in some order. None are lost or duplicated, and
+/// each is dropped exactly once. The tests check this by panicking at every
+/// comparison in turn.
+pub trait Sorter<T> {
+    /// A short name that's unique among all of the sorters, like
+    /// `"quick-ninther"`.
+    fn name(&self) -> &'static str;
+
+    /// Whether items that compare equal keep their original order.
+    fn stable(&self) -> bool;
+
+    /// Whether this sorts without a buffer that grows with the number of items.
+    ///
+    /// `O(lg N)` stack space for recursion still counts as in place.
+    fn in_place(&self) -> bool;
+
+    /// How the time taken grows with the number of items, in the worst case.
+    fn worst_case(&self) -> Complexity;
+
+    /// What the items need to support to be sorted.
+    fn requires(&self) -> Requires;
+
+    /// Sorts `items` in ascending order.
+    fn sort(&self, items: &mut [T]);
+}
+
+/// Worst case time complexity classes, for `N` items.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Complexity {
+    /// `O(N)`, not counting terms that only depend on the keys.
+    ///
+    /// Radix sort is really `O(N * K)` for `K` bytes of key, and counting sort
+    /// is `O(N + K)` for a range of `K` keys.
+    Linear,
+
+    /// `O(N lg N)`, which is the best that comparison sorts can do.
+    NLogN,
+
+    /// `O(N lg^2 N)`
+    NLogSquaredN,
+
+    /// `O(N^(a/b))`, for `Power(a, b)`.
+    Power(u8, u8),
+
+    /// `O(N^2)`
+    Quadratic,
+}
+
+impl fmt::Display for Complexity {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Complexity::Linear => write!(fmt, "O(N)"),
+            Complexity::NLogN => write!(fmt, "O(N lg N)"),
+            Complexity::NLogSquaredN => write!(fmt, "O(N lg^2 N)"),
+            Complexity::Power(a, b) => write!(fmt, "O(N^({}/{}))", a, b),
+            Complexity::Quadratic => write!(fmt, "O(N^2)"),
+        }
+    }
+}
+
+/// What a [`Sorter`] needs from the items that it sorts.
+///
+/// [`sorters`] requires both `Ord` and `RadixKey`, and that they put items in
+/// the same order, so that every sort can run on the same items. Likewise,
+/// [`string_sorters`] expects strings' bytes to be in the same order as `Ord`
+/// puts the strings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Requires {
+    /// Items are compared with `Ord`.
+    Ord,
+
+    /// Items are sorted by their [`RadixKey`] bytes, and never compared.
+    RadixKey,
+
+    /// Like `RadixKey`, but the keys must fall in a range small enough to keep
+    /// a count for each one.
+    SmallRange,
+
+    /// Like `RadixKey`, but the keys are converted to `f64`, and must fit in
+    /// one exactly. They should also be spread uniformly, or this gets slow.
+    Float,
+    /// Items are byte strings, sorted by their bytes with `AsRef<[u8]>`, and
+    /// never compared.
+    Bytes,
+}
+
+impl Requires {
+    /// Whether `items` are fit to be sorted by a sort that requires this.
+    ///
+    /// Sorts may panic, or get the order wrong, on items that aren't. Only
+    /// `SmallRange` and `Float` ask anything of the items themselves.
+    ///
+    /// ```
+    /// use algos::Requires;
+    ///
+    /// assert!(Requires::SmallRange.admits(&[-5_i64, 1_000]));
+    /// assert!(!Requires::SmallRange.admits(&[i64::MIN, i64::MAX]));
+    /// assert!(!Requires::Float.admits(&[u64::MAX]));
+    /// ```
+    pub fn admits<T: RadixKey>(self, items: &[T]) -> bool {
+        match self {
+            Requires::Ord | Requires::RadixKey | Requires::Bytes => true,
+            Requires::SmallRange => key_span(items)
+                .1
+                .is_some_and(|len| len <= MAX_COUNTING_RANGE),
+            Requires::Float => items
+                .iter()
+                .all(|x| radix_value(x) <= 1 << f64::MANTISSA_DIGITS),
+        }
+    }
+}
+
+/// A [`Sorter`] made from a sort function, and the facts about it.
+pub struct SortFn<T> {
+    pub name: &'static str,
+    pub stable: bool,
+    pub in_place: bool,
+    pub worst_case: Complexity,
+    pub requires: Requires,
+    pub sort: fn(&mut [T]),
+}
+
+impl<T> Sorter<T> for SortFn<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn stable(&self) -> bool {
+        self.stable
+    }
+
+    fn in_place(&self) -> bool {
+        self.in_place
+    }
+
+    fn worst_case(&self) -> Complexity {
+        self.worst_case
+    }
+
+    fn requires(&self) -> Requires {
+        self.requires
+    }
+
+    fn sort(&self, items: &mut [T]) {
+        (self.sort)(items)
+    }
+}
+
+// `fn(&mut [T])` is `Copy` for any `T`, but deriving would require `T: Copy`.
+impl<T> Clone for SortFn<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SortFn<T> {}
+
+impl<T> fmt::Debug for SortFn<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SortFn")
+            .field("name", &self.name)
+            .field("stable", &self.stable)
+            .field("in_place", &self.in_place)
+            .field("worst_case", &self.worst_case)
+            .field("requires", &self.requires)
+            .finish()
+    }
+}
+
+macro_rules! sorters {
+    ($($name:expr, $stable:expr, $in_place:expr, $worst_case:expr, $requires:ident => $sort:expr;)+) => {
+        vec![$(
+            SortFn {
+                name: $name,
+                stable: $stable,
+                in_place: $in_place,
+                worst_case: $worst_case,
+                requires: Requires::$requires,
+                sort: $sort,
+            },
+        )+]
+    };
+}
+
+/// Every sort in this crate, with each of its options, plus the standard
+/// library's sorts to compare against.
+///
+/// This is [`comparison_sorters`] followed by [`key_sorters`], so `T` needs to
+/// be both `Ord` and `RadixKey`. Types that are only `Ord`, like `String` or
+/// anything that isn't `Copy`, can use `comparison_sorters` on its own.
+///
+/// New sorts should be added to one of those. That's all it takes for them to
+/// be tested and benchmarked.
+///
+/// ```
+/// use algos::{sorters, Sorter};
+///
+/// for sorter in sorters() {
+///     let mut items = vec![3, 1, 2];
+///     sorter.sort(&mut items);
+///     assert_eq!(items, [1, 2, 3], "{} is broken", sorter.name());
+/// }
+/// ```
+pub fn sorters<T: Ord + RadixKey + Send>() -> Vec<SortFn<T>> {
+    let mut sorters = comparison_sorters();
+    sorters.extend(key_sorters());
+    sorters
+}
+
+/// Every sort in this crate that compares items with `Ord`, plus the standard
+/// library's sorts.
+///
+/// ```
+/// use algos::{comparison_sorters, Sorter};
+///
+/// for sorter in comparison_sorters() {
+///     let mut items = vec!["b".to_string(), "c".to_string(), "a".to_string()];
+///     sorter.sort(&mut items);
+///     assert_eq!(items, ["a", "b", "c"], "{} is broken", sorter.name());
+/// }
+/// ```
+pub fn comparison_sorters<T: Ord + Send>() -> Vec<SortFn<T>> {
+    use Complexity::*;
+
+    // name                  stable  in place  worst case            requires      sort
+    sorters! {
+        "std-stable",        true,   false,    NLogN,                Ord =>        |v| v.sort();
+        "std-unstable",      false,  true,     NLogN,                Ord =>        |v| v.sort_unstable();
+        "selection",         false,  true,     Quadratic,            Ord =>        selection_sort;
+        "insertion",         true,   true,     Quadratic,            Ord =>        insertion_sort;
+        "binary-insertion",  true,   true,     Quadratic,            Ord =>        binary_insertion_sort;
+        "shell",             false,  true,     Quadratic,            Ord =>        shell_sort;
+        "shell-shell",       false,  true,     Quadratic,            Ord =>
+            |v| ShellSort { gaps: GapSequence::Shell }.sort(v);
+        "shell-knuth",       false,  true,     Power(3, 2),          Ord =>
+            |v| ShellSort { gaps: GapSequence::Knuth }.sort(v);
+        "shell-sedgewick",   false,  true,     Power(4, 3),          Ord =>
+            |v| ShellSort { gaps: GapSequence::Sedgewick }.sort(v);
+        "shell-tokuda",      false,  true,     Quadratic,            Ord =>
+            |v| ShellSort { gaps: GapSequence::Tokuda }.sort(v);
+        "merge",             true,   false,    NLogN,                Ord =>        merge_sort;
+        "merge-sqrt-buffer", true,   false,    NLogSquaredN,         Ord =>
+            |v| MergeSort { strategy: MergeStrategy::SqrtBuffer }.sort(v);
+        "merge-in-place",    true,   true,     NLogSquaredN,         Ord =>
+            |v| MergeSort { strategy: MergeStrategy::InPlace }.sort(v);
+        "tim",               true,   false,    NLogN,                Ord =>        tim_sort;
+        "quick",             false,  true,     NLogN,                Ord =>        quick_sort;
+        "quick-last",        false,  true,     NLogN,                Ord =>
+            |v| QuickSort { pivot: PivotStrategy::Last, ..QuickSort::default() }.sort(v);
+        "quick-ninther",     false,  true,     NLogN,                Ord =>
+            |v| QuickSort { pivot: PivotStrategy::Ninther, ..QuickSort::default() }.sort(v);
+        "quick-random",      false,  true,     NLogN,                Ord =>
+            |v| QuickSort { pivot: PivotStrategy::Random { seed: 0x5EED }, ..QuickSort::default() }.sort(v);
+        "quick-3way",        false,  true,     NLogN,                Ord =>
+            |v| QuickSort { partition: PartitionScheme::ThreeWay, ..QuickSort::default() }.sort(v);
+        "quick-block",       false,  true,     NLogN,                Ord =>
+            |v| QuickSort { partition: PartitionScheme::Block, ..QuickSort::default() }.sort(v);
+        "heap",              false,  true,     NLogN,                Ord =>        heap_sort;
+        "par-merge",         true,   false,    NLogN,                Ord =>        par_merge_sort;
+        "par-quick",         false,  true,     NLogN,                Ord =>        par_quick_sort;
+    }
+}
+
+/// Every sort in this crate that sorts by [`RadixKey`] bytes, without
+/// comparing items.
+///
+/// Some of these need more from the keys than `RadixKey` promises. Check
+/// [`Requires::admits`] before sorting with them.
+pub fn key_sorters<T: RadixKey>() -> Vec<SortFn<T>> {
+    use Complexity::*;
+
+    // name                  stable  in place  worst case            requires      sort
+    sorters! {
+        "radix",             true,   false,    Linear,               RadixKey =>   radix_sort;
+        "counting",          true,   false,    Linear,               SmallRange => counting_sort_by_radix_key;
+        "bucket",            true,   false,    Quadratic,            Float =>
+            |v| bucket_sort_by_key(v, |x| radix_value(x) as f64);
+    }
+}
+
+/// Every sort in this crate that sorts byte strings by their bytes, without
+/// comparing them.
+///
+/// `&str`, `String` and `Vec<u8>` all work. Add [`comparison_sorters`] to
+/// compare these against the rest.
+pub fn string_sorters<S: AsRef<[u8]>>() -> Vec<SortFn<S>> {
+    use Complexity::*;
+
+    // name                  stable  in place  worst case            requires      sort
+    sorters! {
+        "msd-radix",         false,  true,     Linear,               Bytes =>      msd_radix_sort;
+        "multikey-quick",    false,  true,     Linear,               Bytes =>      multikey_quick_sort;
+        "burst",             false,  false,    Linear,               Bytes =>      burst_sort;
+    }
+}
+
+/// The most keys that `Requires::SmallRange` admits, which is 8 MiB of counts.
+const MAX_COUNTING_RANGE: u128 = 1 << 20;
+
+/// The smallest key in `items`, and how many keys there are from that to the
+/// largest, unless that's more than a `u128` holds.
+fn key_span<T: RadixKey>(items: &[T]) -> (u128, Option<u128>) {
+    let min = items.iter().map(radix_value).min().unwrap_or(0);
+    let max = items.iter().map(radix_value).max().unwrap_or(0);
+    (min, (max - min).checked_add(1))
+}
+
+/// Counting sort, over the range of keys that `items` actually has.
+///
+/// # Panics
+///
+/// Panics if `Requires::SmallRange` doesn't admit `items`.
+fn counting_sort_by_radix_key<T: RadixKey>(items: &mut [T]) {
+    let (min, len) = key_span(items);
+    let len = len
+        .filter(|&len| len <= MAX_COUNTING_RANGE)
+        .expect("keys span too many values to count");
+
+    counting_sort_by_key(items, 0..len as usize, |x| (radix_value(x) - min) as usize);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    #[test]
+    fn check_names_are_unique() {
+        let mut names: Vec<_> = sorters::<i32>().iter().map(|s| s.name()).collect();
+        names.extend(string_sorters::<String>().iter().map(|s| s.name()));
+        let len = names.len();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), len);
+    }
+
+    #[test]
+    fn check_complexity_display() {
+        assert_eq!(Complexity::NLogN.to_string(), "O(N lg N)");
+        assert_eq!(Complexity::Power(3, 2).to_string(), "O(N^(3/2))");
+    }
+
+    #[test]
+    fn check_counting_sort_negative_keys() {
+        let mut items = vec![3_i8, -128, 127, 0, -1, 3];
+        counting_sort_by_radix_key(&mut items);
+        assert_eq!(items, [-128, -1, 0, 3, 3, 127]);
+    }
+
+    #[test]
+    #[should_panic(expected = "keys span too many values to count")]
+    fn check_counting_sort_wide_ranges() {
+        // Every `u128`, which is one more than a `u128` holds
+        counting_sort_by_radix_key(&mut [u128::MAX, 0]);
+    }
+
+    #[test]
+    fn check_key_sorters_skip_what_they_cant_sort() {
+        fn check<T: Ord + RadixKey + core::fmt::Debug>(items: &[T], admitted: &[&str]) {
+            let mut expected = items.to_vec();
+            expected.sort();
+
+            for sorter in key_sorters::<T>() {
+                let admits = sorter.requires().admits(items);
+                assert_eq!(
+                    admits,
+                    admitted.contains(&sorter.name()),
+                    "{}",
+                    sorter.name()
+                );
+                if admits {
+                    let mut v = items.to_vec();
+                    sorter.sort(&mut v);
+                    assert_eq!(v, expected, "{}", sorter.name());
+                }
+            }
+        }
+
+        check(
+            &[3_i8, -128, 127, 0, -1, 3],
+            &["radix", "counting", "bucket"],
+        );
+        check(&[-1_i32 << 30, 7, 1 << 30], &["radix", "bucket"]);
+        check(&[-1_i64 << 40, 7, 1 << 40], &["radix"]);
+        check(&[1_u64 << 40, 7, 1 << 20], &["radix", "bucket"]);
+
+        // These round to the same `f64`, and are too far apart to count
+        let base = 1_u64 << 60;
+        check(&[base + 3, base + 1, 0, base + 2], &["radix"]);
+        check(&[u128::MAX, 7, 0, u128::MAX], &["radix"]);
+        check(&[i128::MAX, -1, i128::MIN, 0], &["radix"]);
+    }
+}
@@ -0,0 +1,428 @@
+use core::cmp::Ordering;
+
+use super::{
+    insertion_sort_by, log2, partition_three_way, quick_sort_by, PivotChooser, PivotStrategy,
+    INSERTION_SORT_THRESHOLD,
+};
+use crate::heap::BinaryHeap;
+use crate::stats;
+
+/// Finds the `k`th smallest item, counting from 0, in expected `O(N)` time.
+///
+/// See `select_nth_by`.
+pub fn select_nth<T: Ord>(items: &mut [T], k: usize) -> &mut T {
+    select_nth_by(items, k, T::cmp)
+}
+
+/// Finds the `k`th smallest item according to `compare`, counting from 0, in
+/// expected `O(N)` time.
+///
+/// `items` is reordered so that the `k`th smallest item is at `items[k]`, with
+/// everything before it no greater, and everything after it no less. Neither
+/// side is sorted.
+///
+/// This is quickselect: partition around a random pivot like `quick_sort`, but
+/// only keep going on the side that holds slot `k`. Each partition throws away
+/// a constant fraction of the items on average, so the total work is linear.
+/// If the pivots keep going badly, it switches to the median of medians, so the
+/// worst case is `O(N)` too.
+///
+/// ```
+/// use algos::select_nth;
+///
+/// let mut items = vec![5, 1, 4, 2, 3];
+/// assert_eq!(*select_nth(&mut items, 1), 2);
+/// assert!(items[..1].iter().all(|x| *x <= 2));
+/// assert!(items[2..].iter().all(|x| *x >= 2));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `k >= items.len()`.
+pub fn select_nth_by<T, F>(items: &mut [T], k: usize, mut compare: F) -> &mut T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert!(
+        k < items.len(),
+        "select_nth() index {} is out of range for {} items",
+        k,
+        items.len()
+    );
+
+    let mut pivots = PivotChooser::new(PivotStrategy::Random {
+        seed: items.len() as u64,
+    });
+    let mut depth_limit = 2 * log2(items.len());
+
+    // `k` is always somewhere in `lo..hi`, and everything outside of that is
+    // already on the correct side of it.
+    let (mut lo, mut hi) = (0, items.len());
+    loop {
+        let window = &mut items[lo..hi];
+        if window.len() <= INSERTION_SORT_THRESHOLD {
+            insertion_sort_by(window, &mut compare);
+            break;
+        }
+
+        // We've partitioned too many times - our pivots must be bad.
+        if depth_limit == 0 {
+            median_of_medians(window, k - lo, &mut compare);
+            break;
+        }
+        depth_limit -= 1;
+
+        let pivot = pivots.choose(window, &mut compare);
+        let equal = partition_three_way(window, pivot, &mut compare);
+        match narrow(k, lo, hi, equal) {
+            Some(rest) => (lo, hi) = rest,
+            None => break,
+        }
+    }
+
+    &mut items[k]
+}
+
+/// Like `select_nth`, but deterministic, and `O(N)` even in the worst case.
+///
+/// See `select_nth_median_of_medians_by`.
+pub fn select_nth_median_of_medians<T: Ord>(items: &mut [T], k: usize) -> &mut T {
+    select_nth_median_of_medians_by(items, k, T::cmp)
+}
+
+/// Like `select_nth_by`, but deterministic, and `O(N)` even in the worst case.
+///
+/// This is the median of medians algorithm (Blum, Floyd, Pratt, Rivest and
+/// Tarjan). It picks a pivot that's guaranteed to have at least 30% of the
+/// items on each side:
+///
+/// 1. Split the items into groups of 5, and find the median of each group.
+/// 2. Recursively select the median of those `N/5` medians, and use it as the
+///    pivot. Half of the groups have a median no greater than it, and 3 of the
+///    5 items in those groups are no greater than their median.
+/// 3. Partition around the pivot, and keep going on the side that holds `k`.
+///
+/// That makes the time `T(N) <= T(N/5) + T(7N/10) + O(N)`, which is linear.
+/// It's usually slower than `select_nth` in practice, since the constant is large.
+///
+/// # Panics
+///
+/// Panics if `k >= items.len()`.
+pub fn select_nth_median_of_medians_by<T, F>(items: &mut [T], k: usize, mut compare: F) -> &mut T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert!(
+        k < items.len(),
+        "select_nth_median_of_medians() index {} is out of range for {} items",
+        k,
+        items.len()
+    );
+
+    median_of_medians(items, k, &mut compare);
+    &mut items[k]
+}
+
+/// Returns the part of `items[lo..hi]` that `k` is in, after it was partitioned
+/// with the items equal to the pivot at `equal`, relative to `lo`.
+///
+/// Returns `None` if `k` is one of the equal items, which are all in place.
+fn narrow(k: usize, lo: usize, hi: usize, equal: (usize, usize)) -> Option<(usize, usize)> {
+    let (equal_lo, equal_hi) = (lo + equal.0, lo + equal.1);
+    if k < equal_lo {
+        Some((lo, equal_lo))
+    } else if k >= equal_hi {
+        Some((equal_hi, hi))
+    } else {
+        None
+    }
+}
+
+/// Moves the `k`th smallest item of `items` into `items[k]`, with everything
+/// before it no greater and everything after it no less.
+fn median_of_medians<T, F>(items: &mut [T], k: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let (mut lo, mut hi) = (0, items.len());
+    loop {
+        let window = &mut items[lo..hi];
+        if window.len() <= INSERTION_SORT_THRESHOLD {
+            insertion_sort_by(window, &mut *compare);
+            return;
+        }
+
+        let pivot = pivot_of_medians(window, compare);
+        let equal = partition_three_way(window, pivot, compare);
+        match narrow(k, lo, hi, equal) {
+            Some(rest) => (lo, hi) = rest,
+            None => return,
+        }
+    }
+}
+
+/// Returns the index of the median of the medians of each group of 5 items.
+///
+/// This moves the medians of the groups to the front of `items`.
+fn pivot_of_medians<T, F>(items: &mut [T], compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let groups = items.len() / 5;
+    for group in 0..groups {
+        let start = group * 5;
+        insertion_sort_by(&mut items[start..start + 5], &mut *compare);
+
+        // Slot `group` is never part of a group that we still need
+        stats::swap(items, group, start + 2);
+    }
+
+    let mid = groups / 2;
+    median_of_medians(&mut items[..groups], mid, compare);
+    mid
+}
+
+/// Sorts the smallest `k` items into `items[..k]`, in `O(N + k lg k)` time.
+///
+/// See `partial_sort_by`.
+pub fn partial_sort<T: Ord>(items: &mut [T], k: usize) {
+    partial_sort_by(items, k, T::cmp);
+}
+
+/// Sorts the smallest `k` items, according to `compare`, into `items[..k]`, in
+/// `O(N + k lg k)` time. The rest of the items end up in `items[k..]`, in no
+/// particular order.
+///
+/// This uses `select_nth_by` to find the smallest `k` items, and then sorts
+/// only those with `quick_sort_by`.
+///
+/// ```
+/// use algos::partial_sort;
+///
+/// let mut items = vec![9, 3, 7, 1, 8, 2];
+/// partial_sort(&mut items, 3);
+/// assert_eq!(&items[..3], [1, 2, 3]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `k > items.len()`.
+pub fn partial_sort_by<T, F>(items: &mut [T], k: usize, mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert!(
+        k <= items.len(),
+        "partial_sort() can't sort {} of {} items",
+        k,
+        items.len()
+    );
+
+    if k < items.len() {
+        select_nth_by(items, k, &mut compare);
+    }
+    quick_sort_by(&mut items[..k], compare);
+}
+
+/// Returns the `k` largest items, largest first, using `O(k)` memory.
+///
+/// See `top_k_by`.
+pub fn top_k<T, I>(items: I, k: usize) -> Vec<T>
+where
+    T: Ord,
+    I: IntoIterator<Item = T>,
+{
+    top_k_by(items, k, T::cmp)
+}
+
+/// Returns the `k` largest items according to `compare`, largest first.
+///
+/// Only `k` items are kept at once, in a min heap, so this works on iterators
+/// that are too big to collect. Each item is compared with the smallest item
+/// kept so far, and replaces it if larger. That takes `O(N lg k)` time.
+///
+/// ```
+/// use algos::top_k;
+///
+/// let words = "the quick brown fox jumps over the lazy dog".split(' ');
+/// assert_eq!(top_k(words, 3), ["the", "the", "quick"]);
+/// ```
+pub fn top_k_by<T, I, F>(items: I, k: usize, mut compare: F) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    if k == 0 {
+        return vec![];
+    }
+
+    // Reverse the comparison for a min heap, so the smallest item is on top.
+    let mut heap = BinaryHeap::with_compare(|a: &T, b: &T| compare(b, a));
+    for item in items {
+        if heap.len() < k {
+            heap.push(item);
+        } else {
+            heap.push_pop(item);
+        }
+    }
+
+    // Ascending in reverse is largest first
+    heap.into_sorted_vec()
+}
+
+/// Sorts the `k` items with the smallest keys into `items[..k]`, using `partial_sort_by`.
+pub fn partial_sort_by_key<T, K, F>(items: &mut [T], k: usize, mut f: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    partial_sort_by(items, k, stats::counted(|a: &T, b: &T| f(a).cmp(&f(b))));
+}
+
+/// Returns the `k` items with the largest keys, largest first, using `top_k_by`.
+pub fn top_k_by_key<T, I, K, F>(items: I, k: usize, mut f: F) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    top_k_by(items, k, stats::counted(|a: &T, b: &T| f(a).cmp(&f(b))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::measure;
+    use itertools::Itertools;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    /// Checks that `items[k]` is the `k`th smallest, and split around correctly.
+    fn check_selected(items: &[i32], k: usize, sorted: &[i32]) {
+        assert_eq!(items[k], sorted[k], "k = {}", k);
+        assert!(items[..k].iter().all(|x| *x <= items[k]), "k = {}", k);
+        assert!(items[k + 1..].iter().all(|x| *x >= items[k]), "k = {}", k);
+    }
+
+    #[test]
+    fn check_select_every_permutation() {
+        for items in (0..6).permutations(6) {
+            for k in 0..items.len() {
+                let mut v = items.clone();
+                assert_eq!(*select_nth(&mut v, k), k as i32);
+
+                let mut v = items.clone();
+                assert_eq!(*select_nth_median_of_medians(&mut v, k), k as i32);
+            }
+        }
+    }
+
+    #[test]
+    fn check_select_random() {
+        let mut rng = thread_rng();
+        for len in [1, 17, 100, 1_000, 10_000].iter().copied() {
+            // Plenty of duplicates
+            let items: Vec<i32> = (0..len).map(|_| rng.gen_range(0, len / 4 + 1)).collect();
+            let mut sorted = items.clone();
+            sorted.sort_unstable();
+
+            for _ in 0..10 {
+                let k = rng.gen_range(0, len) as usize;
+
+                let mut v = items.clone();
+                select_nth(&mut v, k);
+                check_selected(&v, k, &sorted);
+
+                let mut v = items.clone();
+                select_nth_median_of_medians(&mut v, k);
+                check_selected(&v, k, &sorted);
+            }
+        }
+    }
+
+    #[test]
+    fn check_select_by() {
+        let mut words = vec!["pear", "fig", "banana", "kiwi", "apple"];
+        let longest = select_nth_by(&mut words, 4, |a, b| a.len().cmp(&b.len()));
+        assert_eq!(*longest, "banana");
+    }
+
+    #[test]
+    fn check_select_is_linear() {
+        const N: usize = 20_000;
+        let inputs: Vec<Vec<i32>> = vec![
+            (0..N as i32).collect(),
+            (0..N as i32).rev().collect(),
+            vec![7; N],
+            (0..N as i32).map(|i| i % 2).collect(),
+        ];
+
+        for items in inputs {
+            for k in [0, N / 2, N - 1].iter().copied() {
+                let stats = measure(&mut items.clone(), |items, compare| {
+                    select_nth_by(items, k, compare);
+                });
+                assert!(stats.comparisons < 10 * N, "{:?}", stats);
+
+                // The worst case bound is about 22 N comparisons, plus sorting
+                // groups of 5.
+                let stats = measure(&mut items.clone(), |items, compare| {
+                    select_nth_median_of_medians_by(items, k, compare);
+                });
+                assert!(stats.comparisons < 40 * N, "{:?}", stats);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn check_select_out_of_range() {
+        select_nth(&mut [1, 2, 3], 3);
+    }
+
+    #[test]
+    fn check_partial_sort() {
+        let mut rng = thread_rng();
+        let mut items: Vec<i32> = (0..1_000).map(|i| i / 2).collect();
+        let sorted = items.clone();
+
+        for k in [0, 1, 10, 500, 999, 1_000].iter().copied() {
+            items.shuffle(&mut rng);
+            partial_sort(&mut items, k);
+            assert_eq!(&items[..k], &sorted[..k]);
+
+            let mut rest = items[k..].to_vec();
+            rest.sort_unstable();
+            assert_eq!(rest, &sorted[k..]);
+        }
+
+        // Sort by the last digit, and keep the three smallest
+        let mut items = vec![19, 42, 33, 71, 28];
+        partial_sort_by_key(&mut items, 3, |x| x % 10);
+        assert_eq!(&items[..3], [71, 42, 33]);
+    }
+
+    #[test]
+    fn check_top_k() {
+        let mut items: Vec<i32> = (0..1_000).map(|i| i / 3).collect();
+        items.shuffle(&mut thread_rng());
+        let mut expected = items.clone();
+        expected.sort_unstable_by(|a, b| b.cmp(a));
+
+        for k in [0, 1, 10, 999, 1_000, 2_000].iter().copied() {
+            let top = top_k(items.iter().copied(), k);
+            assert_eq!(top, &expected[..k.min(items.len())]);
+        }
+
+        // Works on iterators that never get collected
+        assert_eq!(top_k(0..100_000_u64, 3), [99_999, 99_998, 99_997]);
+
+        let smallest = top_k_by(items.iter().copied(), 3, |a, b| b.cmp(a));
+        assert_eq!(smallest, [0, 0, 0]);
+
+        let words = vec!["pear", "fig", "banana", "kiwi"];
+        assert_eq!(top_k_by_key(words, 2, |w| w.len()), ["banana", "pear"]);
+    }
+}
@@ -0,0 +1,240 @@
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+
+use crate::stats;
+
+/// Which gaps `shell_sort` uses, from largest to smallest.
+///
+/// Each sequence is listed here smallest first. `shell_sort` uses the gaps
+/// smaller than the number of items, largest first. Every sequence ends with
+/// a gap of 1, which is a plain insertion sort.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GapSequence<'a> {
+    /// Shell's original `N/2, N/4, ..., 1`. This is `O(N^2)` in the worst case.
+    Shell,
+
+    /// Knuth's `(3^k - 1) / 2`: `1, 4, 13, 40, 121, ...`. This is `O(N^(3/2))`.
+    Knuth,
+
+    /// Sedgewick's 1986 sequence: `1, 5, 19, 41, 109, ...`. This is `O(N^(4/3))`.
+    Sedgewick,
+
+    /// Ciura's empirically found `1, 4, 10, 23, 57, 132, 301, 701`, extended by
+    /// multiplying by 2.25. This is usually the fastest, but has no proven bound.
+    #[default]
+    Ciura,
+
+    /// Tokuda's `ceil((9^k - 4^k) / (5 * 4^(k-1)))`: `1, 4, 9, 20, 46, 103, ...`
+    Tokuda,
+
+    /// Your own gaps, largest first.
+    ///
+    /// These must be strictly decreasing, and end with 1.
+    Custom(&'a [usize]),
+}
+
+/// Ciura's gaps, which were found by experiment and not a formula.
+const CIURA_GAPS: &[usize] = &[1, 4, 10, 23, 57, 132, 301, 701];
+
+impl<'a> GapSequence<'a> {
+    /// Returns the gaps that `shell_sort` uses for `len` items, largest first.
+    ///
+    /// This doesn't allocate.
+    pub fn gaps(self, len: usize) -> impl Iterator<Item = usize> + 'a {
+        if let GapSequence::Custom(gaps) = self {
+            assert!(
+                gaps.last() == Some(&1) && gaps.windows(2).all(|w| w[0] > w[1]),
+                "Custom gap sequences must be strictly decreasing, and end with 1. Found {:?}",
+                gaps
+            );
+        }
+
+        // Count how many gaps we use: every gap smaller than `len`.
+        // There's always at least 1, so that we always finish with insertion sort.
+        let count = (1..)
+            .take_while(|k| self.nth_gap(*k, len).map(|gap| gap < len).unwrap_or(false))
+            .last()
+            .unwrap_or(0)
+            + 1;
+
+        (0..count).rev().filter_map(move |k| self.nth_gap(k, len))
+    }
+
+    /// Returns the `k`th smallest gap, or `None` if it doesn't fit in a `usize`.
+    /// `len` is only used by `Shell`.
+    fn nth_gap(self, k: u32, len: usize) -> Option<usize> {
+        let gap = match self {
+            GapSequence::Shell => {
+                // The gaps are len / 2^j, so the kth smallest is len / 2^(lg(len) - k)
+                let lg = usize::BITS - len.max(1).leading_zeros() - 1;
+                (len >> lg.checked_sub(k)?).max(1)
+            }
+            GapSequence::Knuth => (3_usize.checked_pow(k + 1)? - 1) / 2,
+            GapSequence::Sedgewick => {
+                let k = k as u128;
+                let gap = if k.is_multiple_of(2) {
+                    9 * (1_u128 << k) - 9 * (1_u128 << (k / 2)) + 1
+                } else {
+                    8 * (1_u128 << k) - 6 * (1_u128 << k.div_ceil(2)) + 1
+                };
+                usize::try_from(gap).ok()?
+            }
+            GapSequence::Ciura => match CIURA_GAPS.get(k as usize) {
+                Some(gap) => *gap,
+                None => {
+                    let extra = k as i32 - (CIURA_GAPS.len() as i32 - 1);
+                    let gap = *CIURA_GAPS.last().unwrap() as f64 * 2.25_f64.powi(extra);
+                    if gap >= usize::MAX as f64 {
+                        return None;
+                    }
+                    gap as usize
+                }
+            },
+            GapSequence::Tokuda => {
+                if k >= 40 {
+                    // 9^k overflows a u128 after this
+                    return None;
+                }
+                let k = k + 1;
+                let numerator = 9_u128.pow(k) - 4_u128.pow(k);
+                let denominator = 5 * 4_u128.pow(k - 1);
+                usize::try_from(numerator.div_ceil(denominator)).ok()?
+            }
+            GapSequence::Custom(gaps) => *gaps.get(gaps.len().checked_sub(k as usize + 1)?)?,
+        };
+
+        Some(gap)
+    }
+}
+
+pub fn shell_sort<T: Ord>(items: &mut [T]) {
+    shell_sort_by(items, T::cmp);
+}
+
+/// Shell sort is an insertion sort that moves items long distances first.
+///
+/// This uses the default [`ShellSort`] options. See there for the details.
+pub fn shell_sort_by<T, F>(items: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    ShellSort::default().sort_by(items, compare);
+}
+
+/// Options for `shell_sort`.
+///
+/// Insertion sort only moves items one slot at a time, which is slow when
+/// they're far from home. Shell sort instead insertion sorts every `gap`th item,
+/// for a shrinking sequence of gaps. Early passes move items long distances
+/// quickly, and leave the items nearly sorted for the later passes.
+/// The last gap is 1, which is a plain insertion sort.
+///
+/// This sorts in place, and never allocates.
+///
+/// ```
+/// use algos::{GapSequence, ShellSort};
+///
+/// let mut items = vec![5, 2, 8, 1, 9, 3];
+/// ShellSort {
+///     gaps: GapSequence::Custom(&[3, 1]),
+/// }
+/// .sort(&mut items);
+/// assert_eq!(items, [1, 2, 3, 5, 8, 9]);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShellSort<'a> {
+    pub gaps: GapSequence<'a>,
+}
+
+impl ShellSort<'_> {
+    pub fn sort<T: Ord>(&self, items: &mut [T]) {
+        self.sort_by(items, T::cmp);
+    }
+
+    pub fn sort_by<T, F>(&self, items: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        for gap in self.gaps.gaps(items.len()) {
+            // Insertion sort, but comparing and moving items `gap` apart.
+            for i in gap..items.len() {
+                let mut j = i;
+                while j >= gap && compare(&items[j], &items[j - gap]) == Ordering::Less {
+                    stats::swap(items, j, j - gap);
+                    j -= gap;
+                }
+            }
+        }
+    }
+
+    pub fn sort_by_key<T, K, F>(&self, items: &mut [T], mut f: F)
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.sort_by(items, |a, b| f(a).cmp(&f(b)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    fn gaps(sequence: GapSequence, len: usize) -> Vec<usize> {
+        sequence.gaps(len).collect()
+    }
+
+    #[test]
+    fn check_gap_sequences() {
+        assert_eq!(gaps(GapSequence::Shell, 100), [50, 25, 12, 6, 3, 1]);
+        assert_eq!(gaps(GapSequence::Knuth, 1_000), [364, 121, 40, 13, 4, 1]);
+        assert_eq!(
+            gaps(GapSequence::Sedgewick, 1_000),
+            [929, 505, 209, 109, 41, 19, 5, 1]
+        );
+        assert_eq!(
+            gaps(GapSequence::Ciura, 5_000),
+            [3548, 1577, 701, 301, 132, 57, 23, 10, 4, 1]
+        );
+        assert_eq!(
+            gaps(GapSequence::Tokuda, 1_000),
+            [525, 233, 103, 46, 20, 9, 4, 1]
+        );
+        assert_eq!(gaps(GapSequence::Custom(&[7, 3, 1]), 5), [3, 1]);
+    }
+
+    #[test]
+    fn check_gap_sequences_tiny_and_huge() {
+        let sequences = [
+            GapSequence::Shell,
+            GapSequence::Knuth,
+            GapSequence::Sedgewick,
+            GapSequence::Ciura,
+            GapSequence::Tokuda,
+            GapSequence::Custom(&[1]),
+        ];
+
+        for sequence in sequences.iter().copied() {
+            // We always finish with a gap of 1, even when there's nothing to sort.
+            assert_eq!(gaps(sequence, 0), [1], "{:?}", sequence);
+            assert_eq!(gaps(sequence, 1), [1], "{:?}", sequence);
+
+            // Huge lengths shouldn't overflow, and should still be decreasing.
+            let huge = gaps(sequence, usize::MAX);
+            assert_eq!(huge.last(), Some(&1), "{:?}", sequence);
+            assert!(huge.windows(2).all(|w| w[0] > w[1]), "{:?}", sequence);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "strictly decreasing")]
+    fn check_custom_gaps_must_end_with_one() {
+        ShellSort {
+            gaps: GapSequence::Custom(&[4, 2]),
+        }
+        .sort(&mut [3, 2, 1]);
+    }
+}
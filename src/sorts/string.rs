@@ -0,0 +1,391 @@
+use core::mem;
+
+use super::apply_permutation;
+use super::radix::{common_prefix_len, msd_bucket, sort_suffixes};
+use crate::stats::{self, Scratch};
+use crate::trace::Recursion;
+
+/// Slices this short are handed off to `insertion_sort` by `multikey_quick_sort`.
+const MULTIKEY_INSERTION_SORT_THRESHOLD: usize = 16;
+
+/// Buckets in `burst_sort` that grow past this many strings are burst into a
+/// node of their own, with a bucket for each byte that comes next.
+///
+/// Buckets this size still fit in cache while they're sorted.
+const BURST_THRESHOLD: usize = 4096;
+
+/// Sorts byte strings lexicographically, with Bentley and Sedgewick's
+/// three-way radix quicksort.
+///
+/// This works like `quick_sort` with the `ThreeWay` partition scheme, but on
+/// one byte of the strings at a time. The strings are partitioned around the
+/// byte that a pivot has at `depth`, into those with a smaller byte, the same
+/// byte, and a larger byte. Only the middle part moves on to the next byte,
+/// since the others still differ at this one.
+///
+/// Shared prefixes are only looked at once, which `quick_sort` does over and
+/// over as it compares the same strings again. Unlike `msd_radix_sort`, there's
+/// no count for each of the 256 bytes, so it stays quick when there are few
+/// strings left with each prefix.
+///
+/// `&str`, `String` and `Vec<u8>` all work, and strings sort in the same order
+/// as `Ord` puts them. Shorter strings sort before any longer string that they
+/// are a prefix of.
+///
+/// ```
+/// use algos::multikey_quick_sort;
+///
+/// let mut urls = vec![
+///     "https://example.com/b",
+///     "https://example.com/a/2",
+///     "https://example.com/a",
+///     "http://example.com/",
+/// ];
+/// multikey_quick_sort(&mut urls);
+/// assert_eq!(
+///     urls,
+///     [
+///         "http://example.com/",
+///         "https://example.com/a",
+///         "https://example.com/a/2",
+///         "https://example.com/b",
+///     ]
+/// );
+/// ```
+pub fn multikey_quick_sort<S: AsRef<[u8]>>(items: &mut [S]) {
+    multikey_helper(items, 0);
+}
+
+/// Sorts `items`, which all share the same first `depth` bytes.
+fn multikey_helper<S: AsRef<[u8]>>(mut items: &mut [S], mut depth: usize) {
+    loop {
+        let _recursion = Recursion::enter(items);
+
+        if items.len() <= MULTIKEY_INSERTION_SORT_THRESHOLD {
+            sort_suffixes(items, depth);
+            return;
+        }
+
+        // Median of three bytes, so that sorted input splits evenly
+        let byte = |item: &S| msd_bucket(item.as_ref(), depth);
+        let (a, b, c) = (
+            byte(&items[0]),
+            byte(&items[items.len() / 2]),
+            byte(&items[items.len() - 1]),
+        );
+        let pivot = a.min(b).max(a.max(b).min(c));
+
+        // Everything in `..lt` is less than the pivot, `lt..i` is equal to it,
+        // and `gt..` is greater. `i..gt` hasn't been looked at yet.
+        let (mut lt, mut i, mut gt) = (0, 0, items.len());
+        while i < gt {
+            let b = byte(&items[i]);
+            if b < pivot {
+                stats::swap(items, lt, i);
+                lt += 1;
+                i += 1;
+            } else if b > pivot {
+                gt -= 1;
+                stats::swap(items, i, gt);
+            } else {
+                i += 1;
+            }
+        }
+
+        let (less, rest) = items.split_at_mut(lt);
+        let (equal, greater) = rest.split_at_mut(gt - lt);
+        multikey_helper(less, depth);
+        multikey_helper(greater, depth);
+
+        // Strings that have ended are all equal, so they're done
+        if pivot == 0 {
+            return;
+        }
+
+        // When every string has the same byte here, they probably share more.
+        // Skipping all of it at once saves a pass over them for each byte.
+        depth += 1;
+        if lt == 0 && gt == equal.len() {
+            depth += common_prefix_len(equal, depth);
+        }
+        items = equal;
+    }
+}
+
+/// Sorts byte strings lexicographically, with a burstsort.
+///
+/// This is a most significant digit radix sort that only splits strings up by
+/// their next byte once there are enough of them to be worth it:
+///
+/// 1. Strings are added to a trie, one at a time. Each node has a bucket for
+///    each byte, and each string goes in the bucket for its next byte.
+/// 2. When a bucket gets too big, it bursts: it's replaced with a new node,
+///    and its strings are sorted into that node's buckets by their next byte.
+/// 3. Walking the trie in order of its bytes visits the buckets in sorted
+///    order. Each bucket is sorted with `multikey_quick_sort` on the way.
+///
+/// Busy prefixes get deep tries, so the buckets all end up small, and are
+/// sorted while they fit in cache. Rare prefixes stay in one bucket, and never
+/// pay for 256 buckets of their own. Bytes that every string under a node
+/// shares, like the `https://www.` of URLs, are kept once in that node, rather
+/// than as a chain of nodes with one byte each.
+///
+/// The trie holds each string's bytes and index, not the string itself. So
+/// this takes `O(N)` scratch memory, plus the trie's nodes. The items are only
+/// moved at the end, with `apply_permutation`, so they don't need to be
+/// `Clone`.
+///
+/// `&str`, `String` and `Vec<u8>` all work, and strings sort in the same order
+/// as `Ord` puts them.
+///
+/// ```
+/// use algos::burst_sort;
+///
+/// let mut paths: Vec<String> = vec![
+///     "/usr/lib/libz.so".into(),
+///     "/usr/bin/rustc".into(),
+///     "/usr/bin/cargo".into(),
+///     "/etc/hosts".into(),
+/// ];
+/// burst_sort(&mut paths);
+/// assert_eq!(
+///     paths,
+///     ["/etc/hosts", "/usr/bin/cargo", "/usr/bin/rustc", "/usr/lib/libz.so"]
+/// );
+/// ```
+pub fn burst_sort<S: AsRef<[u8]>>(items: &mut [S]) {
+    if items.len() < 2 {
+        return;
+    }
+
+    let _scratch = Scratch::new::<(Entry, usize)>(items.len());
+    let mut root = BurstNode::new(&[]);
+    for (index, item) in items.iter().enumerate() {
+        let bytes = item.as_ref();
+        root.insert(Entry { bytes, index }, 0);
+    }
+
+    let mut order = Vec::with_capacity(items.len());
+    root.sort_into(0, &mut order);
+    apply_permutation(items, &order);
+}
+
+/// A string in a `burst_sort` trie, and where it came from.
+struct Entry<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl AsRef<[u8]> for Entry<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.bytes
+    }
+}
+
+/// A node in a `burst_sort` trie, for strings that share the same first
+/// `depth` bytes, and then `prefix`.
+struct BurstNode<'a> {
+    /// Bytes that every string under this node has in common.
+    ///
+    /// Long shared prefixes, like `https://www.`, would otherwise need a node
+    /// for each byte, which every string has to walk through.
+    prefix: &'a [u8],
+
+    /// Strings with no more bytes, which all sort first, and are equal.
+    ended: Vec<Entry<'a>>,
+
+    /// What to do with strings for each next byte.
+    children: Vec<BurstChild<'a>>,
+}
+
+enum BurstChild<'a> {
+    /// Strings that haven't been sorted on their next byte yet.
+    Bucket(Vec<Entry<'a>>),
+
+    /// A bucket that burst.
+    Node(Box<BurstNode<'a>>),
+}
+
+impl<'a> BurstNode<'a> {
+    fn new(prefix: &'a [u8]) -> Self {
+        BurstNode {
+            prefix,
+            ended: vec![],
+            children: (0..256).map(|_| BurstChild::Bucket(vec![])).collect(),
+        }
+    }
+
+    /// Adds `entry` to the trie under this node, which is at `depth`.
+    fn insert(&mut self, entry: Entry<'a>, depth: usize) {
+        // A string that doesn't share all of the prefix splits this node where
+        // it stops matching.
+        let rest = entry.bytes.get(depth..).unwrap_or_default();
+        let shared = self
+            .prefix
+            .iter()
+            .zip(rest)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if shared < self.prefix.len() {
+            self.split(shared);
+        }
+
+        let depth = depth + self.prefix.len();
+        let byte = match entry.bytes.get(depth) {
+            Some(&byte) => byte as usize,
+            None => {
+                self.ended.push(entry);
+                return;
+            }
+        };
+
+        match &mut self.children[byte] {
+            BurstChild::Node(child) => child.insert(entry, depth + 1),
+            BurstChild::Bucket(bucket) => {
+                bucket.push(entry);
+                if bucket.len() > BURST_THRESHOLD {
+                    let bucket = mem::take(bucket);
+                    let shared = common_prefix_len(&bucket, depth + 1);
+                    let prefix = &bucket[0].bytes[depth + 1..depth + 1 + shared];
+
+                    let mut child = BurstNode::new(prefix);
+                    for entry in bucket {
+                        child.insert(entry, depth + 1);
+                    }
+                    self.children[byte] = BurstChild::Node(Box::new(child));
+                }
+            }
+        }
+    }
+
+    /// Shortens the prefix to its first `len` bytes, and moves everything
+    /// under this node to a new child, for the byte after them.
+    fn split(&mut self, len: usize) {
+        let prefix = self.prefix;
+        let mut child = mem::replace(self, BurstNode::new(&prefix[..len]));
+        child.prefix = &prefix[len + 1..];
+        self.children[prefix[len] as usize] = BurstChild::Node(Box::new(child));
+    }
+
+    /// Appends the index of every string under this node, which is at
+    /// `depth`, to `order`, in sorted order.
+    fn sort_into(self, depth: usize, order: &mut Vec<usize>) {
+        let depth = depth + self.prefix.len();
+        order.extend(self.ended.iter().map(|entry| entry.index));
+        for child in self.children {
+            match child {
+                BurstChild::Bucket(mut bucket) => {
+                    multikey_helper(&mut bucket, depth + 1);
+                    order.extend(bucket.iter().map(|entry| entry.index));
+                }
+                BurstChild::Node(node) => node.sort_into(depth + 1, order),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    /// Strings over a small alphabet, with long shared prefixes and repeats.
+    fn make_strings(rng: &mut SmallRng, len: usize) -> Vec<String> {
+        let prefixes = ["", "a", "https://example.com/", "https://example.com/a/"];
+        (0..len)
+            .map(|_| {
+                let prefix = prefixes[rng.gen_range(0, prefixes.len())];
+                let suffix: String = (0..rng.gen_range(0, 6))
+                    .map(|_| ['a', 'b', '/', 'é', '\u{10348}'][rng.gen_range(0, 5)])
+                    .collect();
+                format!("{}{}", prefix, suffix)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_string_sorts() {
+        type Sort = fn(&mut [String]);
+        let sorts: [(&str, Sort); 2] = [
+            ("multikey-quick", multikey_quick_sort),
+            ("burst", burst_sort),
+        ];
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        for len in [0, 1, 2, 10, 100, 1_000, 3 * BURST_THRESHOLD] {
+            let strings = make_strings(&mut rng, len);
+            let mut expected = strings.clone();
+            expected.sort();
+
+            for (name, sort) in sorts.iter() {
+                let mut items = strings.clone();
+                sort(&mut items);
+                assert!(items == expected, "{} failed on {} strings", name, len);
+            }
+        }
+    }
+
+    #[test]
+    fn check_string_sorts_on_strs_and_bytes() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let strings = make_strings(&mut rng, 2 * BURST_THRESHOLD);
+        let mut expected = strings.clone();
+        expected.sort();
+
+        let mut strs: Vec<&str> = strings.iter().map(|s| s.as_str()).collect();
+        multikey_quick_sort(&mut strs);
+        assert!(strs == expected);
+
+        let mut strs: Vec<&str> = strings.iter().map(|s| s.as_str()).collect();
+        burst_sort(&mut strs);
+        assert!(strs == expected);
+
+        let expected: Vec<Vec<u8>> = expected.into_iter().map(String::into_bytes).collect();
+        let bytes: Vec<Vec<u8>> = strings.into_iter().map(String::into_bytes).collect();
+
+        let mut items = bytes.clone();
+        multikey_quick_sort(&mut items);
+        assert!(items == expected);
+
+        let mut items = bytes;
+        burst_sort(&mut items);
+        assert!(items == expected);
+    }
+
+    #[test]
+    fn check_burst_sort_bursts_repeatedly() {
+        // Every string shares a long prefix, and many are repeated, so buckets
+        // keep bursting until the strings run out
+        let prefix = "/home/user/projects/algos/src/".repeat(4);
+        let mut items: Vec<String> = (0..4 * BURST_THRESHOLD)
+            .map(|i| format!("{}{}", prefix, i % (BURST_THRESHOLD / 2)))
+            .collect();
+        let mut expected = items.clone();
+        expected.sort();
+
+        burst_sort(&mut items);
+        assert_eq!(items, expected);
+    }
+
+    #[test]
+    fn check_burst_sort_splits_shared_prefixes() {
+        // The first bucket to burst shares all of `prefix`. Then strings that
+        // stop matching partway through it, or end inside it, split it up.
+        let prefix = "/home/user/projects/algos/src/";
+        let mut items: Vec<String> = (0..2 * BURST_THRESHOLD)
+            .map(|i| format!("{}{}", prefix, i))
+            .collect();
+        for cut in (1..prefix.len()).rev() {
+            items.push(prefix[..cut].to_string());
+            items.push(format!("{}~{}", &prefix[..cut], cut));
+        }
+        let mut expected = items.clone();
+        expected.sort();
+
+        burst_sort(&mut items);
+        assert_eq!(items, expected);
+    }
+}
@@ -0,0 +1,728 @@
+use core::cmp::Ordering;
+
+use crate::stats::{self, Scratch};
+
+use super::merge::MergeHole;
+
+/// Slices shorter than this are sorted as a single run, with `insertion_sort`.
+const MIN_MERGE: usize = 64;
+
+/// How many times in a row one side of a merge must win before we start galloping.
+const MIN_GALLOP: usize = 7;
+
+pub fn tim_sort<T: Ord>(items: &mut [T]) {
+    tim_sort_by(items, T::cmp);
+}
+
+/// Timsort is a stable, adaptive merge sort. It runs in `O(N)` on sorted input,
+/// and `O(N lg N)` in the worst case.
+///
+/// Real data often has some order to it, so instead of blindly splitting the
+/// items in half, Timsort looks for "runs" that are already sorted:
+///
+/// 1. Walk the items and find the next run. Strictly descending runs are
+///    reversed in place to make them ascending.
+/// 2. Short runs are extended to a minimum length with `insertion_sort`.
+/// 3. Push the run onto a stack, and merge runs on the stack until their
+///    lengths shrink at least as fast as the Fibonacci numbers. This keeps
+///    merges balanced, and the stack short.
+/// 4. When a merge is lopsided, and one side keeps winning, switch to
+///    "galloping": exponentially search for how far that side keeps winning,
+///    and move that whole block at once.
+///
+/// Merging moves items instead of cloning them, using a scratch buffer half the
+/// size of `items`.
+pub fn tim_sort_by<T, F>(items: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut is_less = |a: &T, b: &T| compare(a, b) == Ordering::Less;
+    let len = items.len();
+
+    // Zero sized items all look the same, so there's nothing to sort.
+    // (And our pointer math below can't handle them.)
+    if len < 2 || core::mem::size_of::<T>() == 0 {
+        return;
+    }
+
+    // Small slices are one run, which we extend with insertion sort.
+    if len < MIN_MERGE {
+        let run = count_run(items, &mut is_less);
+        insertion_sort_from(items, run, &mut is_less);
+        return;
+    }
+
+    let min_run = min_run_length(len);
+    let mut tim = TimSort {
+        runs: vec![],
+        buffer: Vec::with_capacity(len / 2),
+        min_gallop: MIN_GALLOP,
+    };
+    let _scratch = Scratch::new::<T>(tim.buffer.capacity());
+
+    let mut start = 0;
+    while start < len {
+        // 1. Find the next run
+        let mut run_len = count_run(&mut items[start..], &mut is_less);
+
+        // 2. Extend short runs so that we don't merge lots of tiny runs
+        if run_len < min_run {
+            let forced = min_run.min(len - start);
+            insertion_sort_from(&mut items[start..start + forced], run_len, &mut is_less);
+            run_len = forced;
+        }
+
+        // 3. Push it and keep the stack balanced
+        tim.runs.push(Run {
+            start,
+            len: run_len,
+        });
+        tim.merge_collapse(items, &mut is_less);
+
+        start += run_len;
+    }
+
+    // Merge whatever is left into one run
+    tim.merge_force_collapse(items, &mut is_less);
+    debug_assert_eq!(tim.runs.len(), 1);
+}
+
+/// Picks a minimum run length for `len` items in `MIN_MERGE/2..=MIN_MERGE`.
+///
+/// It's chosen so that `len / min_run` is a power of 2, or just under one,
+/// which keeps the final merges balanced.
+fn min_run_length(mut len: usize) -> usize {
+    let mut r = 0;
+    while len >= MIN_MERGE {
+        r |= len & 1;
+        len >>= 1;
+    }
+
+    len + r
+}
+
+/// Returns the length of the run at the start of `items`.
+///
+/// Runs are either ascending (`a <= b <= c`) or strictly descending (`a > b > c`).
+/// Descending runs are reversed, so the run is ascending when this returns.
+/// They must be strictly descending, or reversing them would break stability.
+fn count_run<T, F>(items: &mut [T], is_less: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    if items.len() < 2 {
+        return items.len();
+    }
+
+    let mut end = 2;
+    if is_less(&items[1], &items[0]) {
+        while end < items.len() && is_less(&items[end], &items[end - 1]) {
+            end += 1;
+        }
+        for i in 0..end / 2 {
+            stats::swap(items, i, end - 1 - i);
+        }
+    } else {
+        while end < items.len() && !is_less(&items[end], &items[end - 1]) {
+            end += 1;
+        }
+    }
+
+    end
+}
+
+/// Insertion sort for `items`, whose first `sorted` items are already sorted.
+fn insertion_sort_from<T, F>(items: &mut [T], sorted: usize, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    for i in sorted.max(1)..items.len() {
+        for j in (1..=i).rev() {
+            if is_less(&items[j], &items[j - 1]) {
+                stats::swap(items, j, j - 1);
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/// Returns how many items at the start of `run` go before our key.
+///
+/// `goes_before` tells whether an item goes before the key. It must be true for
+/// some prefix of `run`, and false for the rest.
+///
+/// Instead of a plain binary search, this first checks items 1, 2, 4, 8, ... away
+/// from one end, which takes `O(lg K)` compares to find an answer `K` items from
+/// that end. Merges expect answers near the start (or the end) of a run, so
+/// this beats a binary search over the whole run.
+fn gallop<T>(run: &[T], from_end: bool, mut goes_before: impl FnMut(&T) -> bool) -> usize {
+    let len = run.len();
+
+    // Narrow our search down to `lo..hi`
+    let (lo, hi) = if !from_end {
+        if len == 0 || !goes_before(&run[0]) {
+            return 0;
+        }
+
+        // `run[last]` always goes before the key
+        let mut last = 0;
+        let mut offset = 1;
+        loop {
+            let probe = last + offset;
+            if probe >= len {
+                break (last + 1, len);
+            }
+            if goes_before(&run[probe]) {
+                last = probe;
+                offset *= 2;
+            } else {
+                break (last + 1, probe);
+            }
+        }
+    } else {
+        if len == 0 || goes_before(&run[len - 1]) {
+            return len;
+        }
+
+        // `run[first]` never goes before the key
+        let mut first = len - 1;
+        let mut offset = 1;
+        loop {
+            if offset > first {
+                break (0, first);
+            }
+            let probe = first - offset;
+            if goes_before(&run[probe]) {
+                break (probe + 1, first);
+            } else {
+                first = probe;
+                offset *= 2;
+            }
+        }
+    };
+
+    lo + run[lo..hi].partition_point(goes_before)
+}
+
+/// A sorted run of items, at `items[start..start + len]`
+#[derive(Copy, Clone, Debug)]
+struct Run {
+    start: usize,
+    len: usize,
+}
+
+struct TimSort<T> {
+    /// Runs that still need to be merged, in order.
+    runs: Vec<Run>,
+
+    /// Scratch space for merges. This always has a length of 0: we only use its
+    /// capacity, and never drop anything that we move into it.
+    buffer: Vec<T>,
+
+    /// How many wins in a row start galloping.
+    /// This adapts: data that gallops well lowers it, and data that doesn't raises it.
+    min_gallop: usize,
+}
+
+impl<T> TimSort<T> {
+    /// Merges runs until the stack satisfies these invariants, for the top runs
+    /// `A`, `B`, `C`, `D` (with `D` on top):
+    ///
+    /// - `A > B + C`
+    /// - `B > C + D`
+    /// - `C > D`
+    ///
+    /// Checking 4 runs deep, and not just 3, fixes the bug in the original
+    /// Timsort found by de Gouw et al. in 2015.
+    fn merge_collapse<F>(&mut self, items: &mut [T], is_less: &mut F)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        while self.runs.len() > 1 {
+            let runs = &self.runs;
+            let mut n = runs.len() - 2;
+
+            if (n >= 1 && runs[n - 1].len <= runs[n].len + runs[n + 1].len)
+                || (n >= 2 && runs[n - 2].len <= runs[n - 1].len + runs[n].len)
+            {
+                // Merge the middle run with the smaller of its neighbors
+                if runs[n - 1].len < runs[n + 1].len {
+                    n -= 1;
+                }
+            } else if runs[n].len > runs[n + 1].len {
+                // Invariants hold
+                break;
+            }
+
+            self.merge_at(n, items, is_less);
+        }
+    }
+
+    /// Merges every run left on the stack.
+    fn merge_force_collapse<F>(&mut self, items: &mut [T], is_less: &mut F)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        while self.runs.len() > 1 {
+            let mut n = self.runs.len() - 2;
+            if n >= 1 && self.runs[n - 1].len < self.runs[n + 1].len {
+                n -= 1;
+            }
+
+            self.merge_at(n, items, is_less);
+        }
+    }
+
+    /// Merges `runs[n]` and `runs[n + 1]`.
+    fn merge_at<F>(&mut self, n: usize, items: &mut [T], is_less: &mut F)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let a = self.runs[n];
+        let b = self.runs.remove(n + 1);
+        self.runs[n].len += b.len;
+
+        let (left, right) = items[a.start..b.start + b.len].split_at_mut(a.len);
+
+        // Items at the start of `left` that are <= everything in `right` are
+        // already in place. Stability means ties stay on the left.
+        let skip_left = gallop(left, false, |x| !is_less(&right[0], x));
+        if skip_left == left.len() {
+            return;
+        }
+
+        // Items at the end of `right` that are >= everything in `left` are also
+        // already in place.
+        let left_last = &left[left.len() - 1];
+        let keep_right = gallop(right, true, |x| is_less(x, left_last));
+        if keep_right == 0 {
+            return;
+        }
+
+        let start = a.start + skip_left;
+        let mid = a.len - skip_left;
+        let end = b.start + keep_right;
+
+        // Copy whichever run is shorter into our buffer.
+        if mid <= keep_right {
+            self.merge_lo(&mut items[start..end], mid, is_less);
+        } else {
+            self.merge_hi(&mut items[start..end], mid, is_less);
+        }
+    }
+
+    /// Merges `items[..mid]` and `items[mid..]`, working from the front.
+    /// `items[..mid]` must be the shorter run.
+    fn merge_lo<F>(&mut self, items: &mut [T], mid: usize, is_less: &mut F)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let len = items.len();
+        let v = items.as_mut_ptr();
+        let buf = self.buffer.as_mut_ptr();
+        debug_assert!(mid <= self.buffer.capacity());
+
+        // SAFETY:
+        // We move the left run into `buf`, which leaves a hole in `items` where it was.
+        // Items are moved from `buf` or the right run into the front of the hole,
+        // which slides the hole to the right until it's gone.
+        //
+        // `hole` tracks which items are still in `buf`, and where they go.
+        // If `is_less` panics, `hole` is dropped and copies them all back, so
+        // `items` never has duplicated or missing items.
+        unsafe {
+            stats::copy_nonoverlapping(v, buf, mid);
+            let mut hole = MergeHole {
+                start: buf,
+                end: buf.add(mid),
+                dest: v,
+            };
+
+            // The right run hasn't moved.
+            let mut right = v.add(mid);
+            let right_end = v.add(len);
+
+            // Both runs start out with at least one item, and we stop as soon as
+            // either runs out.
+            let mut min_gallop = self.min_gallop;
+            'merge: loop {
+                // One item at a time, until one side keeps winning
+                let mut left_wins = 0;
+                let mut right_wins = 0;
+                loop {
+                    // Ties take from the left, to stay stable
+                    if is_less(&*right, &*hole.start) {
+                        stats::copy_nonoverlapping(right, hole.dest, 1);
+                        right = right.add(1);
+                        right_wins += 1;
+                        left_wins = 0;
+                    } else {
+                        stats::copy_nonoverlapping(hole.start, hole.dest, 1);
+                        hole.start = hole.start.add(1);
+                        left_wins += 1;
+                        right_wins = 0;
+                    }
+                    hole.dest = hole.dest.add(1);
+
+                    if hole.start == hole.end || right == right_end {
+                        break 'merge;
+                    }
+                    if left_wins >= min_gallop || right_wins >= min_gallop {
+                        break;
+                    }
+                }
+
+                // Galloping, until neither side wins by much
+                loop {
+                    // Move every left item <= the next right item
+                    let left_run = slice_between(hole.start, hole.end);
+                    let count = gallop(left_run, false, |x| !is_less(&*right, x));
+                    stats::copy_nonoverlapping(hole.start, hole.dest, count);
+                    hole.start = hole.start.add(count);
+                    hole.dest = hole.dest.add(count);
+                    if hole.start == hole.end {
+                        break 'merge;
+                    }
+                    let left_count = count;
+
+                    // Which means that the next right item is next
+                    stats::copy_nonoverlapping(right, hole.dest, 1);
+                    right = right.add(1);
+                    hole.dest = hole.dest.add(1);
+                    if right == right_end {
+                        break 'merge;
+                    }
+
+                    // Move every right item < the next left item
+                    let right_run = slice_between(right, right_end);
+                    let count = gallop(right_run, false, |x| is_less(x, &*hole.start));
+                    stats::copy(right, hole.dest, count);
+                    right = right.add(count);
+                    hole.dest = hole.dest.add(count);
+                    if right == right_end {
+                        break 'merge;
+                    }
+                    let right_count = count;
+
+                    // Which means that the next left item is next
+                    stats::copy_nonoverlapping(hole.start, hole.dest, 1);
+                    hole.start = hole.start.add(1);
+                    hole.dest = hole.dest.add(1);
+                    if hole.start == hole.end {
+                        break 'merge;
+                    }
+
+                    // Galloping is working, so make it easier to start next time
+                    min_gallop = min_gallop.saturating_sub(1);
+                    if left_count < MIN_GALLOP && right_count < MIN_GALLOP {
+                        break;
+                    }
+                }
+
+                // Galloping stopped working, so make it harder to start next time
+                min_gallop += 2;
+            }
+            self.min_gallop = min_gallop.max(1);
+
+            // Dropping `hole` moves anything left in `buf` into place.
+            // Anything left in the right run is already in place.
+        }
+    }
+
+    /// Merges `items[..mid]` and `items[mid..]`, working from the back.
+    /// `items[mid..]` must be the shorter run.
+    fn merge_hi<F>(&mut self, items: &mut [T], mid: usize, is_less: &mut F)
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let len = items.len();
+        let v = items.as_mut_ptr();
+        let buf = self.buffer.as_mut_ptr();
+        debug_assert!(len - mid <= self.buffer.capacity());
+
+        // SAFETY:
+        // This mirrors `merge_lo`: we move the right run into `buf`, and fill the
+        // hole that it leaves from the back. The largest remaining item goes last.
+        //
+        // `hole.start..hole.end` are the items still in `buf`. They belong right
+        // after whatever is left of the left run, at `hole.dest`.
+        unsafe {
+            stats::copy_nonoverlapping(v.add(mid), buf, len - mid);
+            let mut hole = MergeHole {
+                start: buf,
+                end: buf.add(len - mid),
+                dest: v.add(mid),
+            };
+
+            // The left run hasn't moved, and `hole.dest` is always its end.
+            // We fill `items` backwards from `out`.
+            let mut out = v.add(len);
+
+            // Both runs start out with at least one item, and we stop as soon as
+            // either runs out.
+            let mut min_gallop = self.min_gallop;
+            'merge: loop {
+                // One item at a time, until one side keeps winning
+                let mut left_wins = 0;
+                let mut right_wins = 0;
+                loop {
+                    let left_last = hole.dest.sub(1);
+                    let right_last = hole.end.sub(1);
+                    out = out.sub(1);
+
+                    // Ties take from the right, since we're filling from the back
+                    if is_less(&*right_last, &*left_last) {
+                        stats::copy(left_last, out, 1);
+                        hole.dest = left_last;
+                        left_wins += 1;
+                        right_wins = 0;
+                    } else {
+                        stats::copy_nonoverlapping(right_last, out, 1);
+                        hole.end = right_last;
+                        right_wins += 1;
+                        left_wins = 0;
+                    }
+
+                    if hole.start == hole.end || hole.dest == v {
+                        break 'merge;
+                    }
+                    if left_wins >= min_gallop || right_wins >= min_gallop {
+                        break;
+                    }
+                }
+
+                // Galloping, until neither side wins by much
+                loop {
+                    // Move every left item > the last right item
+                    let left_run = slice_between(v, hole.dest);
+                    let right_last = hole.end.sub(1);
+                    let count =
+                        left_run.len() - gallop(left_run, true, |x| !is_less(&*right_last, x));
+                    out = out.sub(count);
+                    hole.dest = hole.dest.sub(count);
+                    stats::copy(hole.dest, out, count);
+                    if hole.dest == v {
+                        break 'merge;
+                    }
+                    let left_count = count;
+
+                    // Which means that the last right item is next
+                    out = out.sub(1);
+                    hole.end = right_last;
+                    stats::copy_nonoverlapping(right_last, out, 1);
+                    if hole.start == hole.end {
+                        break 'merge;
+                    }
+
+                    // Move every right item >= the last left item
+                    let right_run = slice_between(hole.start, hole.end);
+                    let left_last = hole.dest.sub(1);
+                    let count =
+                        right_run.len() - gallop(right_run, true, |x| is_less(x, &*left_last));
+                    out = out.sub(count);
+                    hole.end = hole.end.sub(count);
+                    stats::copy_nonoverlapping(hole.end, out, count);
+                    if hole.start == hole.end {
+                        break 'merge;
+                    }
+                    let right_count = count;
+
+                    // Which means that the last left item is next
+                    out = out.sub(1);
+                    hole.dest = left_last;
+                    stats::copy(left_last, out, 1);
+                    if hole.dest == v {
+                        break 'merge;
+                    }
+
+                    // Galloping is working, so make it easier to start next time
+                    min_gallop = min_gallop.saturating_sub(1);
+                    if left_count < MIN_GALLOP && right_count < MIN_GALLOP {
+                        break;
+                    }
+                }
+
+                // Galloping stopped working, so make it harder to start next time
+                min_gallop += 2;
+            }
+            self.min_gallop = min_gallop.max(1);
+
+            // Dropping `hole` moves anything left in `buf` into place.
+            // Anything left in the left run is already in place.
+        }
+    }
+}
+
+/// Makes a slice out of the items from `start` up to (not including) `end`.
+///
+/// # Safety
+///
+/// `start..end` must be valid, initialized items, and `start <= end`.
+unsafe fn slice_between<'a, T>(start: *const T, end: *const T) -> &'a [T] {
+    core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    /// Sorts `items` with `tim_sort`, and returns how many compares it took.
+    fn count_compares(items: &mut [i32]) -> usize {
+        let mut compares = 0;
+        tim_sort_by(items, |a, b| {
+            compares += 1;
+            a.cmp(b)
+        });
+
+        compares
+    }
+
+    #[test]
+    fn check_min_run_length() {
+        assert_eq!(min_run_length(63), 63);
+        assert_eq!(min_run_length(64), 32);
+        assert_eq!(min_run_length(65), 33);
+        assert_eq!(min_run_length(1 << 20), 32);
+
+        for len in 64..10_000 {
+            let min_run = min_run_length(len);
+            assert!((MIN_MERGE / 2..=MIN_MERGE).contains(&min_run));
+        }
+    }
+
+    #[test]
+    fn check_gallop() {
+        let run: Vec<i32> = (0..100).collect();
+        for key in -1..=100 {
+            let expected = run.iter().filter(|x| **x < key).count();
+            assert_eq!(gallop(&run, false, |x| *x < key), expected);
+            assert_eq!(gallop(&run, true, |x| *x < key), expected);
+        }
+
+        assert_eq!(gallop(&[] as &[i32], false, |_| true), 0);
+        assert_eq!(gallop(&[] as &[i32], true, |_| true), 0);
+    }
+
+    #[test]
+    fn check_sorted_is_linear() {
+        let mut items: Vec<i32> = (0..10_000).collect();
+        assert_eq!(count_compares(&mut items), items.len() - 1);
+
+        let mut items: Vec<i32> = (0..10_000).rev().collect();
+        assert_eq!(count_compares(&mut items), items.len() - 1);
+        assert_eq!(items, (0..10_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn check_descending_runs_must_be_strict() {
+        // Reversing a run with ties would swap the ties, so `count_run` stops at them.
+        let mut items = vec![(3, 'a'), (2, 'a'), (2, 'b'), (1, 'a')];
+        let run = count_run(&mut items, &mut |a, b| a.0 < b.0);
+        assert_eq!(run, 2);
+        assert_eq!(&items[..2], [(2, 'a'), (3, 'a')]);
+    }
+
+    #[test]
+    fn check_galloping_merges() {
+        // Two long runs, where one side wins for long stretches at a time.
+        // Galloping should move those stretches without comparing every item.
+        let mut items: Vec<i32> = (0..5_000).map(|i| (i / 500) * 1_000 + i % 500).collect();
+        items.extend((0..5_000).map(|i| (i / 500) * 1_000 + 500 + i % 500));
+        let mut expected = items.clone();
+        expected.sort();
+
+        let compares = count_compares(&mut items);
+        assert_eq!(items, expected);
+
+        // Finding the runs takes N compares. Merging them one at a time would take another N.
+        assert!(
+            compares < items.len() + items.len() / 4,
+            "{} compares",
+            compares
+        );
+    }
+
+    #[test]
+    fn check_merge_hi_and_lo() {
+        // Vary the lengths of two interleaved runs so that both sides get to
+        // be the shorter one, and both sides get to gallop.
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let left_len = rng.gen_range(64, 2_000);
+            let right_len = rng.gen_range(64, 2_000);
+            let mut left: Vec<(i32, usize)> =
+                (0..left_len).map(|i| (rng.gen_range(0, 100), i)).collect();
+            let mut right: Vec<(i32, usize)> = (0..right_len)
+                .map(|i| (rng.gen_range(0, 100), left_len + i))
+                .collect();
+            left.sort();
+            right.sort();
+
+            let mut items = left;
+            items.extend(right);
+            let mut expected = items.clone();
+            expected.sort();
+
+            // Sorting by just the first field must keep the second field in order.
+            crate::tim_sort_by_key(&mut items, |(k, _i)| *k);
+            assert_eq!(items, expected);
+        }
+    }
+
+    #[test]
+    fn check_random_runs() {
+        // Sorted and reversed blocks of varying sizes make runs of every length,
+        // and lots of ties. Strings are heap allocated, so moving one wrong
+        // won't go unnoticed.
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let len = rng.gen_range(0, 3_000);
+            let dups = rng.gen_range(1, 50);
+            let shape = rng.gen_range(0, 3);
+
+            let mut items: Vec<(String, usize)> = (0..len)
+                .map(|i| {
+                    let key = match shape {
+                        0 => rng.gen_range(0, dups),
+                        1 => i / dups,
+                        _ => (len - i) / dups,
+                    };
+                    (format!("{:05}", key), i)
+                })
+                .collect();
+            let mut expected = items.clone();
+            expected.sort_by(|a, b| a.0.cmp(&b.0));
+
+            tim_sort_by(&mut items, |a, b| a.0.cmp(&b.0));
+            assert_eq!(items, expected);
+        }
+    }
+
+    #[test]
+    fn check_panic_keeps_items() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut items: Vec<Box<i32>> = (0..1_000).map(|i| Box::new((i * 7919) % 1_000)).collect();
+        let mut compares = 0;
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            tim_sort_by(&mut items, |a, b| {
+                compares += 1;
+                if compares == 5_000 {
+                    panic!("Oops");
+                }
+                a.cmp(b)
+            })
+        }));
+        assert!(result.is_err());
+
+        // Every item should still be there, exactly once.
+        let mut items: Vec<i32> = items.into_iter().map(|b| *b).collect();
+        items.sort();
+        assert_eq!(items, (0..1_000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,641 @@
+//! Counts the work that a sort does, so that it can be checked against the analysis.
+//!
+//! Wall time depends on the machine, but the number of comparisons, swaps and
+//! writes doesn't. [`measure`] runs a sort and counts them:
+//!
+//! ```
+//! use algos::selection_sort_by;
+//! use algos::stats;
+//!
+//! let mut items: Vec<i32> = (0..100).rev().collect();
+//! let stats = stats::measure(&mut items, |items, compare| selection_sort_by(items, compare));
+//!
+//! // Selection sort always does N(N-1)/2 comparisons
+//! assert_eq!(stats.comparisons, 100 * 99 / 2);
+//! assert_eq!(stats.peak_scratch_bytes, 0);
+//! ```
+//!
+//! Comparisons are counted by wrapping the comparator that `measure` passes to
+//! the sort, and any comparator that a sort builds for itself, like those in the
+//! `_by_key` variants. Calling a plain `Ord` sort like `selection_sort()` inside
+//! `measure` won't count its comparisons, so use the `_by` variant instead.
+//!
+//! Everything else is counted by the sorts themselves, as they work.
+use core::cell::Cell;
+use core::cmp::Ordering;
+use core::ptr;
+use core::sync::atomic::{self, AtomicUsize};
+
+use crate::trace;
+
+/// The work that a sort did, as counted by [`measure`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SortStats {
+    /// How many times two items were compared.
+    pub comparisons: usize,
+
+    /// How many times two items traded places. Every swap is also two writes.
+    pub swaps: usize,
+
+    /// How many times an item was written into a slot, either in the items
+    /// being sorted or in scratch memory.
+    pub writes: usize,
+
+    /// The most scratch memory allocated at once, in bytes.
+    ///
+    /// This counts buffers of items or keys, and the tables of counts that
+    /// radix and counting sorts use. It doesn't count the stack.
+    pub peak_scratch_bytes: usize,
+}
+
+/// How many threads are measuring or tracing right now.
+///
+/// Sorts run on every other thread can check this instead of the thread locals,
+/// which keeps the hooks below nearly free when nothing is being watched.
+pub(crate) static WATCHING: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// What this thread's `measure` has counted so far, if it's measuring.
+    static COUNTERS: Cell<Option<Counters>> = const { Cell::new(None) };
+}
+
+#[derive(Copy, Clone, Default)]
+struct Counters {
+    stats: SortStats,
+    scratch_bytes: usize,
+}
+
+/// Runs `sort` on `items`, and returns how much work it did.
+///
+/// `sort` gets the items, and a comparator that counts its calls. It should
+/// pass that comparator on to a `_by` sort.
+pub fn measure<T, S>(items: &mut [T], sort: S) -> SortStats
+where
+    T: Ord,
+    S: FnOnce(&mut [T], &mut dyn FnMut(&T, &T) -> Ordering),
+{
+    measure_by(items, T::cmp, sort)
+}
+
+/// Like [`measure`], but `sort` gets a comparator that calls `compare`.
+pub fn measure_by<T, C, S>(items: &mut [T], compare: C, sort: S) -> SortStats
+where
+    C: FnMut(&T, &T) -> Ordering,
+    S: FnOnce(&mut [T], &mut dyn FnMut(&T, &T) -> Ordering),
+{
+    let measuring = Measuring::start();
+    sort(items, &mut counted(compare));
+    measuring.finish()
+}
+
+/// Swaps counters in for this thread while it's alive.
+///
+/// This puts back whatever was there before when dropped, even if the sort
+/// panics. A `measure` inside of another adds its counts to the outer one.
+struct Measuring {
+    outer: Option<Counters>,
+}
+
+impl Measuring {
+    fn start() -> Self {
+        WATCHING.fetch_add(1, atomic::Ordering::Relaxed);
+        let outer = COUNTERS.with(|counters| counters.replace(Some(Counters::default())));
+
+        Measuring { outer }
+    }
+
+    fn finish(self) -> SortStats {
+        COUNTERS
+            .with(|counters| counters.get())
+            .unwrap_or_default()
+            .stats
+    }
+}
+
+impl Drop for Measuring {
+    fn drop(&mut self) {
+        let inner = COUNTERS.with(|counters| counters.get()).unwrap_or_default();
+        let outer = self.outer.map(|mut outer| {
+            let (stats, inner) = (&mut outer.stats, &inner.stats);
+            stats.comparisons += inner.comparisons;
+            stats.swaps += inner.swaps;
+            stats.writes += inner.writes;
+            stats.peak_scratch_bytes = stats
+                .peak_scratch_bytes
+                .max(outer.scratch_bytes + inner.peak_scratch_bytes);
+            outer
+        });
+
+        COUNTERS.with(|counters| counters.set(outer));
+        WATCHING.fetch_sub(1, atomic::Ordering::Relaxed);
+    }
+}
+
+/// Whether any thread might be measuring or tracing.
+#[inline]
+fn watching() -> bool {
+    WATCHING.load(atomic::Ordering::Relaxed) != 0
+}
+
+/// Whether this thread is measuring or tracing.
+///
+/// Sorts that would hand work to other threads check this, and keep it all on
+/// this one instead, so that none of it goes uncounted.
+pub(crate) fn watched() -> bool {
+    watching() && (COUNTERS.with(|counters| counters.get().is_some()) || trace::recording())
+}
+
+/// Updates this thread's counters, if it's measuring.
+#[inline]
+fn record(update: impl FnOnce(&mut Counters)) {
+    if !watching() {
+        return;
+    }
+
+    COUNTERS.with(|counters| {
+        if let Some(mut current) = counters.get() {
+            update(&mut current);
+            counters.set(Some(current));
+        }
+    });
+}
+
+/// Wraps `compare`, so that each call is counted as a comparison.
+pub(crate) fn counted<T, F>(mut compare: F) -> impl FnMut(&T, &T) -> Ordering
+where
+    T: ?Sized,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    move |a: &T, b: &T| {
+        if watching() {
+            compared(a, b);
+        }
+        compare(a, b)
+    }
+}
+
+/// Swaps `items[a]` and `items[b]`, and counts it.
+#[inline]
+pub(crate) fn swap<T>(items: &mut [T], a: usize, b: usize) {
+    items.swap(a, b);
+    if watching() {
+        swapped(&items[a], &items[b]);
+    }
+}
+
+/// Counts `count` swaps, for sorts that swap items themselves.
+///
+/// These aren't traced, so sorts shouldn't use this while tracing.
+#[inline]
+pub(crate) fn swaps(count: usize) {
+    record(|c| {
+        c.stats.swaps += count;
+        c.stats.writes += 2 * count;
+    });
+}
+
+/// Counts `count` items being written to scratch memory.
+#[inline]
+pub(crate) fn writes(count: usize) {
+    record(|c| c.stats.writes += count);
+}
+
+/// Counts every item in `items` as written, for sorts that just moved them
+/// themselves.
+#[inline]
+pub(crate) fn wrote<T>(items: &[T]) {
+    if watching() {
+        // SAFETY: `items` are all valid.
+        unsafe { written(items.as_ptr(), items.len()) };
+    }
+}
+
+/// `ptr::copy`, which also counts the items written.
+///
+/// # Safety
+///
+/// The same as `ptr::copy`.
+#[inline]
+pub(crate) unsafe fn copy<T>(src: *const T, dst: *mut T, count: usize) {
+    ptr::copy(src, dst, count);
+    if watching() {
+        written(dst, count);
+    }
+}
+
+/// `ptr::copy_nonoverlapping`, which also counts the items written.
+///
+/// # Safety
+///
+/// The same as `ptr::copy_nonoverlapping`.
+#[inline]
+pub(crate) unsafe fn copy_nonoverlapping<T>(src: *const T, dst: *mut T, count: usize) {
+    ptr::copy_nonoverlapping(src, dst, count);
+    if watching() {
+        written(dst, count);
+    }
+}
+
+// The hooks above call these when something is watching. They're kept out of
+// line, so that the hooks stay small enough to inline into the tightest loops.
+
+#[cold]
+#[inline(never)]
+fn compared<T: ?Sized>(a: &T, b: &T) {
+    record(|c| c.stats.comparisons += 1);
+    trace::compared(a, b);
+}
+
+#[cold]
+#[inline(never)]
+fn swapped<T>(a: &T, b: &T) {
+    record(|c| {
+        c.stats.swaps += 1;
+        c.stats.writes += 2;
+    });
+    trace::swapped(a, b);
+}
+
+/// # Safety
+///
+/// `dst` must point to `count` valid items.
+#[cold]
+#[inline(never)]
+unsafe fn written<T>(dst: *const T, count: usize) {
+    record(|c| c.stats.writes += count);
+    trace::written(dst, count);
+}
+
+/// Counts scratch memory for as long as it's alive.
+///
+/// Sorts create one of these next to each buffer that they allocate, and keep
+/// it around until the buffer is dropped.
+pub(crate) struct Scratch {
+    bytes: usize,
+}
+
+impl Scratch {
+    pub(crate) fn new<T>(len: usize) -> Self {
+        let bytes = len * core::mem::size_of::<T>();
+        record(|c| {
+            c.scratch_bytes += bytes;
+            c.stats.peak_scratch_bytes = c.stats.peak_scratch_bytes.max(c.scratch_bytes);
+        });
+
+        Scratch { bytes }
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let bytes = self.bytes;
+        record(|c| c.scratch_bytes = c.scratch_bytes.saturating_sub(bytes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    const N: usize = 1_000;
+
+    fn shuffled() -> Vec<u32> {
+        let mut items: Vec<u32> = (0..N as u32).collect();
+        items.shuffle(&mut SmallRng::seed_from_u64(0x5EED));
+        items
+    }
+
+    fn sorted() -> Vec<u32> {
+        (0..N as u32).collect()
+    }
+
+    fn reversed() -> Vec<u32> {
+        (0..N as u32).rev().collect()
+    }
+
+    /// `N lg N`, rounded up
+    fn n_lg_n(n: usize) -> usize {
+        n * (usize::BITS - (n - 1).leading_zeros()) as usize
+    }
+
+    #[test]
+    fn check_selection_sort() {
+        for mut items in [shuffled(), sorted(), reversed()] {
+            let stats = measure(&mut items, |items, compare| {
+                selection_sort_by(items, compare)
+            });
+
+            // Finding the smallest of the k items left takes k - 1 comparisons,
+            // whatever order they're in.
+            assert_eq!(stats.comparisons, N * (N - 1) / 2);
+            assert_eq!(stats.swaps, N);
+            assert_eq!(stats.writes, 2 * N);
+            assert_eq!(stats.peak_scratch_bytes, 0);
+        }
+    }
+
+    #[test]
+    fn check_insertion_sort() {
+        // Every comparison finds an inversion, and swaps it.
+        let stats = measure(&mut reversed(), |items, compare| {
+            insertion_sort_by(items, compare)
+        });
+        assert_eq!(stats.comparisons, N * (N - 1) / 2);
+        assert_eq!(stats.swaps, N * (N - 1) / 2);
+
+        // Every item is compared with the one before it, and stays put.
+        let stats = measure(&mut sorted(), |items, compare| {
+            insertion_sort_by(items, compare)
+        });
+        assert_eq!(stats.comparisons, N - 1);
+        assert_eq!(stats.swaps, 0);
+
+        // In general, insertion sort swaps once per inversion.
+        let mut items = shuffled();
+        let mut inversions = 0;
+        for i in 0..N {
+            inversions += items[i + 1..].iter().filter(|x| **x < items[i]).count();
+        }
+        let stats = measure(&mut items, |items, compare| {
+            insertion_sort_by(items, compare)
+        });
+        assert_eq!(stats.swaps, inversions);
+        assert!(stats.comparisons < inversions + N);
+        assert_eq!(stats.peak_scratch_bytes, 0);
+    }
+
+    #[test]
+    fn check_binary_insertion_sort() {
+        // A binary search of `i` sorted items takes at most `lg(i) + 1` comparisons.
+        let lg = usize::BITS as usize - N.leading_zeros() as usize;
+        for mut items in [shuffled(), sorted(), reversed()] {
+            let mut inversions = 0;
+            for i in 0..N {
+                inversions += items[i + 1..].iter().filter(|x| **x < items[i]).count();
+            }
+
+            let stats = measure(&mut items, |items, compare| {
+                binary_insertion_sort_by(items, compare)
+            });
+            assert!(stats.comparisons <= N * lg, "{:?}", stats);
+
+            // Each item that moves shifts the items greater than it up by one.
+            assert_eq!(stats.swaps, 0);
+            assert!(stats.writes >= inversions && stats.writes <= inversions + N);
+            assert_eq!(stats.peak_scratch_bytes, 0);
+        }
+    }
+
+    #[test]
+    fn check_shell_sort() {
+        // Sorted input takes one comparison per item, per gap, and no swaps.
+        let stats = measure(&mut sorted(), |items, compare| {
+            shell_sort_by(items, compare)
+        });
+        let expected: usize = GapSequence::Ciura.gaps(N).map(|gap| N - gap).sum();
+        assert_eq!(stats.comparisons, expected);
+        assert_eq!(stats.swaps, 0);
+
+        let stats = measure(&mut shuffled(), |items, compare| {
+            shell_sort_by(items, compare)
+        });
+        assert!(stats.comparisons < N * (N - 1) / 2 / 10, "{:?}", stats);
+        assert_eq!(stats.peak_scratch_bytes, 0);
+    }
+
+    #[test]
+    fn check_heap_sort() {
+        for mut items in [shuffled(), sorted(), reversed()] {
+            let stats = measure(&mut items, |items, compare| heap_sort_by(items, compare));
+
+            // Each sift down does at most 2 comparisons per level it moves
+            // down. Building the heap sifts each node down at most its height,
+            // and those heights add up to less than N. Then each of the N items
+            // popped sifts down at most lg N levels.
+            assert!(stats.comparisons <= 2 * N + 2 * n_lg_n(N), "{:?}", stats);
+            assert!(stats.swaps <= n_lg_n(N), "{:?}", stats);
+            assert_eq!(stats.peak_scratch_bytes, 0);
+        }
+    }
+
+    #[test]
+    fn check_quick_sort() {
+        let stats = measure(&mut shuffled(), |items, compare| {
+            quick_sort_by(items, compare)
+        });
+
+        // Random pivots average `2 N ln N`, about `1.39 N lg N`, comparisons.
+        // Median of three pivots are better than that.
+        assert!(stats.comparisons < 14 * n_lg_n(N) / 10, "{:?}", stats);
+        assert_eq!(stats.peak_scratch_bytes, 0);
+
+        // Bad pivots fall back to heap sort, so this is still `O(N lg N)`.
+        let stats = measure(&mut sorted(), |items, compare| {
+            QuickSort {
+                pivot: PivotStrategy::Last,
+                ..QuickSort::default()
+            }
+            .sort_by(items, compare)
+        });
+        assert!(stats.comparisons < 4 * n_lg_n(N), "{:?}", stats);
+    }
+
+    #[test]
+    fn check_merge_sort() {
+        let size = core::mem::size_of::<u32>();
+
+        for (strategy, scratch) in [
+            (MergeStrategy::Buffered, N / 2),
+            (MergeStrategy::SqrtBuffer, N.isqrt()),
+            (MergeStrategy::InPlace, 0),
+        ]
+        .iter()
+        .copied()
+        {
+            let merge = MergeSort { strategy };
+
+            // Each merge checks whether its runs are already in order, which
+            // they always are.
+            let stats = measure(&mut sorted(), |items, compare| {
+                merge.sort_by(items, compare)
+            });
+            assert_eq!(stats.comparisons, N - 1, "{:?}", strategy);
+            assert_eq!(stats.writes, 0, "{:?}", strategy);
+            assert_eq!(stats.peak_scratch_bytes, scratch * size, "{:?}", strategy);
+
+            let stats = measure(&mut shuffled(), |items, compare| {
+                merge.sort_by(items, compare)
+            });
+            assert_eq!(stats.peak_scratch_bytes, scratch * size, "{:?}", strategy);
+            if strategy == MergeStrategy::Buffered {
+                // Merging N items takes at most N - 1 comparisons, on each of lg N levels.
+                // There are N - 1 merges, and each does one more to check whether
+                // it's needed at all.
+                assert!(stats.comparisons <= n_lg_n(N), "{:?}", stats);
+                // Each level moves half of the items into the buffer, and every item back.
+                assert!(stats.writes <= 3 * n_lg_n(N) / 2, "{:?}", stats);
+            }
+        }
+    }
+
+    #[test]
+    fn check_tim_sort() {
+        let buffer = N / 2 * core::mem::size_of::<u32>();
+
+        // One ascending run, and nothing to merge
+        let stats = measure(&mut sorted(), |items, compare| tim_sort_by(items, compare));
+        assert_eq!(
+            stats,
+            SortStats {
+                comparisons: N - 1,
+                swaps: 0,
+                writes: 0,
+                peak_scratch_bytes: buffer,
+            }
+        );
+
+        // One descending run, which is reversed
+        let stats = measure(&mut reversed(), |items, compare| {
+            tim_sort_by(items, compare)
+        });
+        assert_eq!(
+            stats,
+            SortStats {
+                comparisons: N - 1,
+                swaps: N / 2,
+                writes: N,
+                peak_scratch_bytes: buffer,
+            }
+        );
+
+        // Random items have short runs. Those are extended to at most 64 items
+        // with insertion sort, which is at most `64^2 / 2` comparisons for each
+        // of the `N / 64` runs. Merging them is less than `N lg N`.
+        let stats = measure(&mut shuffled(), |items, compare| {
+            tim_sort_by(items, compare)
+        });
+        assert!(stats.comparisons <= N * 64 / 2 + n_lg_n(N), "{:?}", stats);
+    }
+
+    #[test]
+    fn check_radix_sort() {
+        // radix_sort never compares, so it doesn't need a comparator
+        let stats = measure(&mut shuffled(), |items, _compare| radix_sort(items));
+
+        // `N` doesn't fit in a byte, so there are two passes. Copying into the
+        // scratch buffer is N writes, and each pass is another N.
+        assert_eq!(stats.comparisons, 0);
+        assert_eq!(stats.writes, 3 * N);
+        assert_eq!(
+            stats.peak_scratch_bytes,
+            4 * 256 * core::mem::size_of::<usize>() + N * core::mem::size_of::<u32>()
+        );
+    }
+
+    #[test]
+    fn check_counting_sort() {
+        let stats = measure(&mut shuffled(), |items, _compare| {
+            counting_sort_by_key(items, 0..N, |x| *x as usize)
+        });
+
+        // Applying the sorted keys' permutation to the items swaps into each slot once.
+        assert_eq!(stats.comparisons, 0);
+        assert_eq!(stats.swaps, N);
+    }
+
+    #[test]
+    fn check_sorting_network() {
+        // Every comparator compares once, whatever the items, and swaps if they're out of order.
+        let network = SortingNetwork::best(20);
+        let comparators = network.comparators().len();
+        let stats = measure(&mut sorted()[..20], |items, compare| {
+            network.sort_by(items, compare)
+        });
+        assert_eq!(stats.comparisons, comparators);
+        assert_eq!(stats.swaps, 0);
+
+        let stats = measure(&mut shuffled()[..20], |items, compare| {
+            network.sort_by(items, compare)
+        });
+        assert_eq!(stats.comparisons, comparators);
+        assert!(stats.swaps > 0 && stats.swaps <= comparators);
+        assert_eq!(stats.writes, 2 * stats.swaps);
+    }
+
+    #[test]
+    fn check_key_variants_count_comparisons() {
+        let by = measure(&mut shuffled(), |items, compare| {
+            insertion_sort_by(items, compare)
+        });
+        let by_key = measure(&mut shuffled(), |items, _compare| {
+            insertion_sort_by_key(items, |x| *x)
+        });
+        assert_eq!(by.comparisons, by_key.comparisons);
+
+        // The cached keys are sorted instead of the items, and then swapped into place.
+        let by_cached_key = measure(&mut shuffled(), |items, _compare| {
+            insertion_sort_by_cached_key(items, |x| *x)
+        });
+        assert_eq!(by.comparisons, by_cached_key.comparisons);
+        assert!(by_cached_key.swaps > by.swaps);
+        assert_eq!(
+            by_cached_key.peak_scratch_bytes,
+            N * core::mem::size_of::<(u32, usize)>()
+        );
+    }
+
+    #[test]
+    fn check_nested_and_panicking_measures() {
+        let mut inner = SortStats::default();
+        let outer = measure(&mut sorted(), |items, compare| {
+            insertion_sort_by(items, compare);
+            inner = measure(&mut reversed(), |items, compare| {
+                insertion_sort_by(items, compare)
+            });
+        });
+        assert_eq!(inner.comparisons, N * (N - 1) / 2);
+        assert_eq!(outer.comparisons, N - 1 + inner.comparisons);
+
+        // A panicking sort must not leave this thread measuring.
+        let panicked = std::panic::catch_unwind(|| {
+            measure(&mut shuffled(), |items, _compare| {
+                heap_sort_by(items, |_, _| panic!("oops"))
+            })
+        });
+        assert!(panicked.is_err());
+        assert!(COUNTERS.with(|counters| counters.get()).is_none());
+
+        // Sorting outside of `measure` isn't counted by anything.
+        heap_sort(&mut shuffled());
+    }
+
+    #[test]
+    fn check_parallel_sorts_are_measured() {
+        // Long enough that they'd use more than one thread, if it weren't measured
+        let mut rng = SmallRng::seed_from_u64(0x5EED);
+        let original: Vec<u32> = (0..20_000).map(|_| rng.gen()).collect();
+
+        for threads in [1, 4] {
+            let merge = measure(&mut original.clone(), |items, _| {
+                ParMergeSort { threads }.sort(items)
+            });
+            let quick = measure(&mut original.clone(), |items, _| {
+                ParQuickSort {
+                    threads,
+                    ..Default::default()
+                }
+                .sort(items)
+            });
+            assert!(merge.writes > 0 && quick.swaps > 0);
+
+            let mut items = original.clone();
+            let serial = measure(&mut items, |items, _| merge_sort(items));
+            assert_eq!(merge.writes, serial.writes, "{} threads", threads);
+        }
+    }
+}
@@ -0,0 +1,872 @@
+//! Records each step that a sort takes, so that it can be shown and checked.
+//!
+//! [`trace`] runs a sort, and records every comparison, swap and write that it
+//! makes to the items, along with where recursive sorts start and finish work
+//! on part of them:
+//!
+//! ```
+//! use algos::insertion_sort_by;
+//! use algos::trace::{self, Event};
+//!
+//! let mut items = vec![2, 3, 1];
+//! let trace = trace::trace(&mut items, |items, compare| insertion_sort_by(items, compare));
+//!
+//! assert_eq!(
+//!     trace.events(),
+//!     [
+//!         Event::Compare(Some(1), Some(0)),
+//!         Event::Compare(Some(2), Some(1)),
+//!         Event::Swap(2, 1),
+//!         Event::Compare(Some(1), Some(0)),
+//!         Event::Swap(1, 0),
+//!     ]
+//! );
+//!
+//! // Replaying the swaps and writes on a copy of the input sorts it again.
+//! assert_eq!(trace.replay(), items);
+//! ```
+//!
+//! Traces can be written out as JSON lines, as a text frame for each step, or
+//! as an animated SVG bar chart.
+//!
+//! Like `stats::measure`, comparisons are only recorded when they go through
+//! the comparator that `trace` passes to the sort, or one that a sort builds
+//! for itself. Swaps, writes and recursion are recorded by the sorts themselves.
+//!
+//! Items are identified by where they are in the slice being sorted. Sorts that
+//! compare keys, or items that they've moved into scratch memory, record `None`
+//! for those, and writes to scratch memory aren't recorded at all. Every write
+//! back into the slice is, along with the item written, so replaying a trace
+//! always gives the same result as the sort did.
+use core::any::Any;
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::fmt::Debug;
+use core::mem;
+use core::ops::Range;
+use core::sync::atomic::{self, AtomicUsize};
+use std::io::{self, Write};
+
+use crate::stats;
+
+/// One step of a sort, as recorded by [`trace`].
+///
+/// Indices are positions in the items being sorted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event<T> {
+    /// Two items were compared.
+    ///
+    /// An index is `None` if that side wasn't in the items, like a key that
+    /// the sort worked out, or an item that's been moved to scratch memory.
+    Compare(Option<usize>, Option<usize>),
+
+    /// Two items traded places.
+    Swap(usize, usize),
+
+    /// An item was written into a slot, from elsewhere in the items, or from
+    /// scratch memory. This holds a copy of what was written.
+    Write(usize, T),
+
+    /// A recursive sort started on these items.
+    Enter(Range<usize>),
+
+    /// A recursive sort finished these items.
+    Exit(Range<usize>),
+}
+
+impl<T: Clone> Event<T> {
+    /// Makes the change to `items` that this event records, if it changed anything.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index is out of bounds for `items`.
+    pub fn apply(&self, items: &mut [T]) {
+        match self {
+            Event::Swap(a, b) => items.swap(*a, *b),
+            Event::Write(i, item) => items[*i] = item.clone(),
+            Event::Compare(..) | Event::Enter(_) | Event::Exit(_) => {}
+        }
+    }
+
+    /// Whether this event looked at or changed `items[i]`.
+    fn touched(&self, i: usize) -> bool {
+        match self {
+            Event::Compare(a, b) => *a == Some(i) || *b == Some(i),
+            Event::Swap(a, b) => *a == i || *b == i,
+            Event::Write(index, _) => *index == i,
+            Event::Enter(range) | Event::Exit(range) => range.contains(&i),
+        }
+    }
+
+    /// A short description for text frames, like `swap 3 4`.
+    fn label(&self) -> String {
+        let index = |i: Option<usize>| i.map_or_else(|| "-".to_string(), |i| i.to_string());
+        match self {
+            Event::Compare(a, b) => format!("compare {} {}", index(*a), index(*b)),
+            Event::Swap(a, b) => format!("swap {} {}", a, b),
+            Event::Write(i, _) => format!("write {}", i),
+            Event::Enter(range) => format!("enter {:?}", range),
+            Event::Exit(range) => format!("exit {:?}", range),
+        }
+    }
+}
+
+/// Every step that a sort took, from [`trace`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace<T> {
+    initial: Vec<T>,
+    events: Vec<Event<T>>,
+}
+
+impl<T: Clone> Trace<T> {
+    /// The items, before they were sorted.
+    pub fn initial(&self) -> &[T] {
+        &self.initial
+    }
+
+    /// What the sort did, in order.
+    pub fn events(&self) -> &[Event<T>] {
+        &self.events
+    }
+
+    /// Applies every event to a copy of the initial items, and returns the result.
+    ///
+    /// That's the same as what the sort left in the items.
+    pub fn replay(&self) -> Vec<T> {
+        let mut items = self.initial.clone();
+        for event in self.events.iter() {
+            event.apply(&mut items);
+        }
+
+        items
+    }
+
+    /// Replays the trace, calling `visit` with each event and the items after it.
+    pub fn replay_with<F>(&self, mut visit: F)
+    where
+        F: FnMut(&Event<T>, &[T]),
+    {
+        let mut items = self.initial.clone();
+        for event in self.events.iter() {
+            event.apply(&mut items);
+            visit(event, &items);
+        }
+    }
+}
+
+impl<T: Clone + Debug> Trace<T> {
+    /// Writes the trace as JSON, with one object per line.
+    ///
+    /// The first line holds the initial items, and each line after that holds
+    /// one event. Items are written as strings, using their `Debug` format.
+    ///
+    /// ```text
+    /// {"event":"start","items":["2","1"]}
+    /// {"event":"compare","i":1,"j":0}
+    /// {"event":"swap","i":1,"j":0}
+    /// ```
+    ///
+    /// A comparison with something outside of the items has `null` for its index.
+    /// Recursion is written as `enter` and `exit` events, with a `start` and `end`.
+    pub fn write_json_lines<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let items: Vec<String> = self.initial.iter().map(json_debug).collect();
+        writeln!(out, r#"{{"event":"start","items":[{}]}}"#, items.join(","))?;
+
+        let index = |i: Option<usize>| i.map_or_else(|| "null".to_string(), |i| i.to_string());
+        for event in self.events.iter() {
+            match event {
+                Event::Compare(a, b) => writeln!(
+                    out,
+                    r#"{{"event":"compare","i":{},"j":{}}}"#,
+                    index(*a),
+                    index(*b)
+                )?,
+                Event::Swap(a, b) => writeln!(out, r#"{{"event":"swap","i":{},"j":{}}}"#, a, b)?,
+                Event::Write(i, item) => writeln!(
+                    out,
+                    r#"{{"event":"write","i":{},"item":{}}}"#,
+                    i,
+                    json_debug(item)
+                )?,
+                Event::Enter(range) => writeln!(
+                    out,
+                    r#"{{"event":"enter","start":{},"end":{}}}"#,
+                    range.start, range.end
+                )?,
+                Event::Exit(range) => writeln!(
+                    out,
+                    r#"{{"event":"exit","start":{},"end":{}}}"#,
+                    range.start, range.end
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the trace as text, with a line for each step showing the items
+    /// after it. The items that the step touched are in brackets.
+    ///
+    /// ```text
+    ///                2   1
+    /// compare 1 0   [2] [1]
+    /// swap 1 0      [1] [2]
+    /// ```
+    pub fn write_frames<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let width = self
+            .initial
+            .iter()
+            .map(|item| format!("{:?}", item).len())
+            .max()
+            .unwrap_or(0);
+        let label_width = self
+            .events
+            .iter()
+            .map(|event| event.label().len())
+            .max()
+            .unwrap_or(0);
+
+        let frame = |out: &mut W, label: &str, items: &[T], touched: &dyn Fn(usize) -> bool| {
+            let mut line = format!("{:<1$}  ", label, label_width);
+            for (i, item) in items.iter().enumerate() {
+                let item = format!("{:>1$?}", item, width);
+                if touched(i) {
+                    line += &format!(" [{}]", item);
+                } else {
+                    line += &format!("  {} ", item);
+                }
+            }
+            writeln!(out, "{}", line.trim_end())
+        };
+
+        frame(&mut out, "", &self.initial, &|_| false)?;
+        let mut result = Ok(());
+        self.replay_with(|event, items| {
+            if result.is_ok() {
+                result = frame(&mut out, &event.label(), items, &|i| event.touched(i));
+            }
+        });
+
+        result
+    }
+}
+
+/// How tall an SVG, and each of its bars, is.
+const SVG_HEIGHT: usize = 200;
+const SVG_BAR_WIDTH: usize = 12;
+
+impl<T: Clone + Ord> Trace<T> {
+    /// Writes the trace as an SVG, with a bar for each item, that animates
+    /// each step for `frame_millis` milliseconds.
+    ///
+    /// Each bar's height is the item's rank, so any `Ord` items can be shown.
+    /// Items being compared are orange, and items being written are red. Parts
+    /// of the items being sorted recursively are light blue.
+    ///
+    /// Every bar has a value for every frame, so this is best kept to tens of
+    /// items.
+    pub fn write_svg<W: Write>(&self, mut out: W, frame_millis: u32) -> io::Result<()> {
+        let len = self.initial.len();
+        let mut ranked = self.initial.clone();
+        ranked.sort();
+        let height = |item: &T| {
+            let rank = ranked.partition_point(|x| x < item) + 1;
+            rank * SVG_HEIGHT / len.max(1)
+        };
+
+        // The heights and colours of every bar, for each frame.
+        let mut heights: Vec<Vec<usize>> = vec![self.initial.iter().map(height).collect()];
+        let mut colours = vec![vec!["steelblue"; len]];
+        self.replay_with(|event, items| {
+            heights.push(items.iter().map(height).collect());
+            colours.push(
+                (0..len)
+                    .map(|i| match event {
+                        _ if !event.touched(i) => "steelblue",
+                        Event::Compare(..) => "orange",
+                        Event::Swap(..) | Event::Write(..) => "crimson",
+                        Event::Enter(_) | Event::Exit(_) => "lightblue",
+                    })
+                    .collect(),
+            );
+        });
+
+        let width = len * SVG_BAR_WIDTH;
+        let duration = heights.len() as u64 * frame_millis as u64;
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            width, SVG_HEIGHT
+        )?;
+        for i in 0..len {
+            let (x, h) = (i * SVG_BAR_WIDTH, heights[0][i]);
+            let frames = |value: &dyn Fn(usize) -> String| {
+                (0..heights.len()).map(value).collect::<Vec<_>>().join(";")
+            };
+
+            writeln!(
+                out,
+                r#"  <rect x="{}" y="{}" width="{}" height="{}" fill="steelblue">"#,
+                x,
+                SVG_HEIGHT - h,
+                SVG_BAR_WIDTH - 1,
+                h
+            )?;
+            for (attribute, values) in [
+                ("y", frames(&|f| (SVG_HEIGHT - heights[f][i]).to_string())),
+                ("height", frames(&|f| heights[f][i].to_string())),
+                ("fill", frames(&|f| colours[f][i].to_string())),
+            ] {
+                writeln!(
+                    out,
+                    r#"    <animate attributeName="{}" values="{}" dur="{}ms" calcMode="discrete" fill="freeze"/>"#,
+                    attribute, values, duration
+                )?;
+            }
+            writeln!(out, "  </rect>")?;
+        }
+        writeln!(out, "</svg>")
+    }
+}
+
+/// An item's `Debug` format, as a JSON string.
+fn json_debug<T: Debug>(item: &T) -> String {
+    let mut json = String::from("\"");
+    for c in format!("{:?}", item).chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Runs `sort` on `items`, and records what it does.
+///
+/// `sort` gets the items, and a comparator that records its calls. It should
+/// pass that comparator on to a `_by` sort.
+///
+/// Items are cloned as they're written, so `clone` shouldn't panic: a sort that
+/// is cleaning up after a panic would abort instead.
+pub fn trace<T, S>(items: &mut [T], sort: S) -> Trace<T>
+where
+    T: Ord + Clone + 'static,
+    S: FnOnce(&mut [T], &mut dyn FnMut(&T, &T) -> Ordering),
+{
+    trace_by(items, T::cmp, sort)
+}
+
+/// Like [`trace`], but `sort` gets a comparator that calls `compare`.
+pub fn trace_by<T, C, S>(items: &mut [T], compare: C, sort: S) -> Trace<T>
+where
+    T: Clone + 'static,
+    C: FnMut(&T, &T) -> Ordering,
+    S: FnOnce(&mut [T], &mut dyn FnMut(&T, &T) -> Ordering),
+{
+    let initial = items.to_vec();
+    let tracing = Tracing::start(Box::new(Recording::<T> {
+        start: items.as_ptr() as usize,
+        len: items.len(),
+        events: Vec::new(),
+    }));
+    sort(items, &mut stats::counted(compare));
+
+    Trace {
+        initial,
+        events: tracing.finish(),
+    }
+}
+
+/// How many threads are tracing right now.
+///
+/// The hooks in `stats` only call in here when something is measuring or
+/// tracing, but sorts can check this to take a slower path that traces better.
+static TRACING: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// What each of this thread's `trace`s has recorded so far, innermost last.
+    static RECORDERS: RefCell<Vec<Box<dyn Recorder>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records events for a [`Recording`], without knowing what type it holds.
+///
+/// Items are passed by address. Sorts sometimes look at items as another type
+/// with the same layout, like `TotalOrder<f64>` for `f64`.
+trait Recorder {
+    /// Where the item at `address` is in the items, if it's one of them.
+    fn index(&self, address: usize) -> Option<usize>;
+
+    fn record(&mut self, event: Event<()>);
+
+    /// Records that `item` was written to `items[index]`.
+    ///
+    /// # Safety
+    ///
+    /// `item` must point to a valid item in the items.
+    unsafe fn record_write(&mut self, index: usize, item: *const u8);
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+struct Recording<T> {
+    /// The address of the items, and how many there are.
+    start: usize,
+    len: usize,
+
+    events: Vec<Event<T>>,
+}
+
+impl<T: Clone + 'static> Recorder for Recording<T> {
+    fn index(&self, address: usize) -> Option<usize> {
+        // Zero sized items all have the same address, but they're all the same.
+        let size = mem::size_of::<T>();
+        let offset = address.wrapping_sub(self.start);
+        if size == 0 || !offset.is_multiple_of(size) || offset / size >= self.len {
+            return None;
+        }
+
+        Some(offset / size)
+    }
+
+    fn record(&mut self, event: Event<()>) {
+        self.events.push(match event {
+            Event::Compare(a, b) => Event::Compare(a, b),
+            Event::Swap(a, b) => Event::Swap(a, b),
+            Event::Enter(range) => Event::Enter(range),
+            Event::Exit(range) => Event::Exit(range),
+            Event::Write(..) => unreachable!("writes are recorded by record_write()"),
+        });
+    }
+
+    unsafe fn record_write(&mut self, index: usize, item: *const u8) {
+        let item = (*(item as *const T)).clone();
+        self.events.push(Event::Write(index, item));
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// Records events on this thread while it's alive.
+///
+/// This stops recording when dropped, even if the sort panics. A `trace` inside
+/// of another records its events in the outer one too, so that both replay.
+struct Tracing;
+
+impl Tracing {
+    fn start(recorder: Box<dyn Recorder>) -> Self {
+        TRACING.fetch_add(1, atomic::Ordering::Relaxed);
+        stats::WATCHING.fetch_add(1, atomic::Ordering::Relaxed);
+        RECORDERS.with(|recorders| recorders.borrow_mut().push(recorder));
+
+        Tracing
+    }
+
+    fn finish<T: 'static>(self) -> Vec<Event<T>> {
+        let recorder = RECORDERS.with(|recorders| recorders.borrow_mut().pop());
+        mem::forget(self);
+        TRACING.fetch_sub(1, atomic::Ordering::Relaxed);
+        stats::WATCHING.fetch_sub(1, atomic::Ordering::Relaxed);
+
+        let recording = recorder
+            .expect("trace() lost its recorder")
+            .into_any()
+            .downcast::<Recording<T>>()
+            .expect("trace() found a recorder for another type");
+        recording.events
+    }
+}
+
+impl Drop for Tracing {
+    fn drop(&mut self) {
+        RECORDERS.with(|recorders| recorders.borrow_mut().pop());
+        TRACING.fetch_sub(1, atomic::Ordering::Relaxed);
+        stats::WATCHING.fetch_sub(1, atomic::Ordering::Relaxed);
+    }
+}
+
+/// Whether any thread might be tracing right now.
+#[inline]
+pub(crate) fn tracing() -> bool {
+    TRACING.load(atomic::Ordering::Relaxed) != 0
+}
+
+/// Whether this thread is tracing.
+pub(crate) fn recording() -> bool {
+    tracing() && RECORDERS.with(|recorders| !recorders.borrow().is_empty())
+}
+
+/// Calls `f` with each of this thread's recorders, if it's tracing.
+#[inline]
+fn with_recorders(mut f: impl FnMut(&mut dyn Recorder)) {
+    if !tracing() {
+        return;
+    }
+
+    RECORDERS.with(|recorders| {
+        for recorder in recorders.borrow_mut().iter_mut() {
+            f(&mut **recorder);
+        }
+    });
+}
+
+/// Records a comparison of `*a` and `*b`.
+pub(crate) fn compared<T: ?Sized>(a: *const T, b: *const T) {
+    with_recorders(|recorder| {
+        let (a, b) = (
+            recorder.index(a as *const u8 as usize),
+            recorder.index(b as *const u8 as usize),
+        );
+        recorder.record(Event::Compare(a, b));
+    });
+}
+
+/// Records that `*a` and `*b` traded places, if they're in the items.
+pub(crate) fn swapped<T>(a: *const T, b: *const T) {
+    with_recorders(|recorder| {
+        let (a, b) = (recorder.index(a as usize), recorder.index(b as usize));
+        if let (Some(a), Some(b)) = (a, b) {
+            recorder.record(Event::Swap(a, b));
+        }
+    });
+}
+
+/// Records that `count` items were written, starting at `dst`.
+///
+/// # Safety
+///
+/// `dst` must point to `count` valid items.
+pub(crate) unsafe fn written<T>(dst: *const T, count: usize) {
+    with_recorders(|recorder| {
+        for i in 0..count {
+            let item = dst.add(i);
+            if let Some(index) = recorder.index(item as usize) {
+                recorder.record_write(index, item as *const u8);
+            }
+        }
+    });
+}
+
+/// Records a recursive sort working on some items, for as long as it's alive.
+///
+/// Recursive sorts create one of these at the start of each call.
+pub(crate) struct Recursion {
+    /// The address of the items, and how many there are
+    items: (usize, usize),
+}
+
+impl Recursion {
+    #[inline]
+    pub(crate) fn enter<T>(items: &[T]) -> Self {
+        let recursion = Recursion {
+            items: (items.as_ptr() as usize, items.len()),
+        };
+        recursion.record(Event::Enter);
+
+        recursion
+    }
+
+    fn record(&self, event: fn(Range<usize>) -> Event<()>) {
+        let (address, len) = self.items;
+        with_recorders(|recorder| {
+            if let Some(start) = recorder.index(address) {
+                recorder.record(event(start..start + len));
+            }
+        });
+    }
+}
+
+impl Drop for Recursion {
+    fn drop(&mut self) {
+        self.record(Event::Exit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[allow(unused_imports)]
+    use pretty_assertions::{assert_eq, assert_ne};
+    use rand::prelude::*;
+
+    fn shuffled(len: usize) -> Vec<u32> {
+        let mut items: Vec<u32> = (0..len as u32).map(|i| i / 3).collect();
+        items.shuffle(&mut SmallRng::seed_from_u64(0x5EED));
+        items
+    }
+
+    /// Checks that `trace` replays to `sorted`, and that its events make sense.
+    fn check_trace(name: &str, trace: &Trace<u32>, sorted: &[u32]) {
+        assert_eq!(trace.replay(), sorted, "{}", name);
+
+        let len = trace.initial().len();
+        let mut recursion = vec![];
+        for event in trace.events() {
+            match event {
+                Event::Compare(a, b) => {
+                    assert!(a.iter().chain(b).all(|i| *i < len), "{} {:?}", name, event)
+                }
+                Event::Swap(a, b) => assert!(*a < len && *b < len, "{} {:?}", name, event),
+                Event::Write(i, _) => assert!(*i < len, "{} {:?}", name, event),
+                Event::Enter(range) => {
+                    let outer = recursion.last().cloned().unwrap_or(0..len);
+                    assert!(
+                        outer.start <= range.start && range.end <= outer.end,
+                        "{} {:?} inside of {:?}",
+                        name,
+                        range,
+                        outer
+                    );
+                    recursion.push(range.clone());
+                }
+                Event::Exit(range) => assert_eq!(recursion.pop().as_ref(), Some(range), "{}", name),
+            }
+        }
+        assert_eq!(recursion, [], "{}", name);
+    }
+
+    #[test]
+    fn check_every_sorter_replays() {
+        for len in [0, 1, 2, 10, 100, 500] {
+            let original = shuffled(len);
+            let mut sorted = original.clone();
+            sorted.sort();
+
+            for sorter in sorters::<u32>() {
+                // The standard library's sorts don't record anything
+                if sorter.name().starts_with("std-") || !sorter.requires().admits(&original) {
+                    continue;
+                }
+
+                let mut items = original.clone();
+                let trace = trace(&mut items, |items, _| sorter.sort(items));
+                assert_eq!(items, sorted, "{}", sorter.name());
+                assert_eq!(trace.initial(), original, "{}", sorter.name());
+                check_trace(sorter.name(), &trace, &sorted);
+            }
+        }
+    }
+
+    #[test]
+    fn check_parallel_sorts_replay() {
+        // Long enough that they'd use more than one thread, if it weren't traced
+        let original = shuffled(20_000);
+        let mut sorted = original.clone();
+        sorted.sort();
+
+        type Sort = fn(&mut [u32]);
+        let sorts: &[(&str, Sort)] = &[
+            ("par-merge", |v| ParMergeSort { threads: 4 }.sort(v)),
+            ("par-quick", |v| {
+                ParQuickSort {
+                    threads: 4,
+                    ..Default::default()
+                }
+                .sort(v)
+            }),
+        ];
+        for (name, sort) in sorts {
+            let mut items = original.clone();
+            let trace = trace(&mut items, |items, _| sort(items));
+            assert_eq!(items, sorted, "{}", name);
+            check_trace(name, &trace, &sorted);
+        }
+    }
+
+    #[test]
+    fn check_comparisons_are_recorded() {
+        type Sort = fn(&mut [u32], &mut dyn FnMut(&u32, &u32) -> Ordering);
+        let sorts: &[(&str, Sort)] = &[
+            ("insertion", |v, c| insertion_sort_by(v, c)),
+            ("binary-insertion", |v, c| binary_insertion_sort_by(v, c)),
+            ("selection", |v, c| selection_sort_by(v, c)),
+            ("heap", |v, c| heap_sort_by(v, c)),
+            ("merge", |v, c| merge_sort_by(v, c)),
+            ("quick", |v, c| quick_sort_by(v, c)),
+            ("shell", |v, c| shell_sort_by(v, c)),
+            ("tim", |v, c| tim_sort_by(v, c)),
+            ("merge-by-key", |v, _| merge_sort_by_key(v, |x| *x)),
+            ("quick-by-cached-key", |v, _| {
+                quick_sort_by_cached_key(v, |x| *x)
+            }),
+        ];
+
+        let original = shuffled(300);
+        let mut sorted = original.clone();
+        sorted.sort();
+        for (name, sort) in sorts {
+            let mut items = original.clone();
+            let measured = stats::measure(&mut items.clone(), *sort);
+            let trace = trace(&mut items, *sort);
+            check_trace(name, &trace, &sorted);
+
+            let comparisons = trace
+                .events()
+                .iter()
+                .filter(|event| matches!(event, Event::Compare(..)))
+                .count();
+            assert_eq!(comparisons, measured.comparisons, "{}", name);
+        }
+    }
+
+    #[test]
+    fn check_network_sort() {
+        let original: Vec<u32> = shuffled(24);
+        let mut items = original.clone();
+        let trace = trace(&mut items, |items, compare| {
+            SortingNetwork::best(24).sort_by(items, compare);
+        });
+
+        let mut sorted = original;
+        sorted.sort();
+        check_trace("network", &trace, &sorted);
+
+        let swaps = trace
+            .events()
+            .iter()
+            .filter(|event| matches!(event, Event::Swap(..)))
+            .count();
+        let measured = stats::measure(&mut trace.initial().to_vec(), |items, compare| {
+            SortingNetwork::best(24).sort_by(items, compare);
+        });
+        assert_eq!(swaps, measured.swaps);
+    }
+
+    #[test]
+    fn check_recursion_is_recorded() {
+        let original = shuffled(100);
+        for (name, sort) in [
+            ("merge", merge_sort as fn(&mut [u32])),
+            ("quick", quick_sort),
+        ] {
+            let mut items = original.clone();
+            let trace = trace(&mut items, |items, _| sort(items));
+            let enters = trace
+                .events()
+                .iter()
+                .filter(|event| matches!(event, Event::Enter(_)))
+                .count();
+            assert!(enters > 1, "{}", name);
+            assert_eq!(trace.events()[0], Event::Enter(0..100), "{}", name);
+        }
+
+        let mut words: Vec<String> = (0..200).map(|i| format!("{:x}", i * 7919 % 1000)).collect();
+        let trace = trace(&mut words, |items, _| msd_radix_sort(items));
+        assert_eq!(trace.events()[0], Event::Enter(0..200));
+        assert_eq!(trace.replay(), words);
+    }
+
+    #[test]
+    fn check_nested_traces() {
+        let mut items = vec![3, 1, 2];
+        let mut inner = None;
+        let outer = trace(&mut items, |items, compare| {
+            inner = Some(trace(items, |items, compare| {
+                insertion_sort_by(items, compare)
+            }));
+            insertion_sort_by(items, compare);
+        });
+
+        // The inner trace sorted everything, so the outer one just checks that
+        let inner = inner.unwrap();
+        assert_eq!(inner.replay(), [1, 2, 3]);
+        assert_eq!(&outer.events()[..inner.events().len()], inner.events());
+        assert_eq!(
+            &outer.events()[inner.events().len()..],
+            [
+                Event::Compare(Some(1), Some(0)),
+                Event::Compare(Some(2), Some(1))
+            ]
+        );
+        assert_eq!(outer.replay(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn check_write_json_lines() {
+        let mut items = vec!["b\"", "a"];
+        let trace = trace(&mut items, |items, compare| merge_sort_by(items, compare));
+        let mut json = vec![];
+        trace.write_json_lines(&mut json).unwrap();
+
+        let mut expected = String::from(r#"{"event":"start","items":["\"b\\\"\"","\"a\""]}"#);
+        expected += "\n";
+        for event in trace.events() {
+            expected += &match event {
+                Event::Compare(a, b) => format!(
+                    r#"{{"event":"compare","i":{},"j":{}}}"#,
+                    a.map_or("null".to_string(), |a| a.to_string()),
+                    b.map_or("null".to_string(), |b| b.to_string())
+                ),
+                Event::Write(i, item) => format!(
+                    r#"{{"event":"write","i":{},"item":{}}}"#,
+                    i,
+                    json_debug(item)
+                ),
+                Event::Enter(r) => {
+                    format!(r#"{{"event":"enter","start":{},"end":{}}}"#, r.start, r.end)
+                }
+                Event::Exit(r) => {
+                    format!(r#"{{"event":"exit","start":{},"end":{}}}"#, r.start, r.end)
+                }
+                Event::Swap(a, b) => format!(r#"{{"event":"swap","i":{},"j":{}}}"#, a, b),
+            };
+            expected += "\n";
+        }
+        assert_eq!(String::from_utf8(json).unwrap(), expected);
+        assert_eq!(json_debug(&"\n"), r#""\"\\n\"""#);
+    }
+
+    #[test]
+    fn check_write_frames() {
+        let mut items = vec![10, 2, 1];
+        let trace = trace(&mut items, |items, compare| {
+            insertion_sort_by(items, compare)
+        });
+        let mut text = vec![];
+        trace.write_frames(&mut text).unwrap();
+
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            [
+                "               10    2    1",
+                "compare 1 0   [10] [ 2]   1",
+                "swap 1 0      [ 2] [10]   1",
+                "compare 2 1     2  [10] [ 1]",
+                "swap 2 1        2  [ 1] [10]",
+                "compare 1 0   [ 2] [ 1]  10",
+                "swap 1 0      [ 1] [ 2]  10",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn check_write_svg() {
+        let mut items = shuffled(20);
+        let trace = trace(&mut items, |items, compare| quick_sort_by(items, compare));
+        let mut svg = vec![];
+        trace.write_svg(&mut svg, 50).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<rect ").count(), 20);
+        assert_eq!(svg.matches("<animate ").count(), 60);
+
+        // Every bar has a value for the start, and for each event
+        let frames = trace.events().len() + 1;
+        let dur = format!(r#"dur="{}ms""#, frames * 50);
+        assert_eq!(svg.matches(&dur).count(), 60);
+        for values in svg.split(r#"values=""#).skip(1) {
+            let values = &values[..values.find('"').unwrap()];
+            assert_eq!(values.split(';').count(), frames);
+        }
+    }
+}